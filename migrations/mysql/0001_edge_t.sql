@@ -0,0 +1,46 @@
+create table if not exists edge_t (
+    id bigint not null auto_increment primary key,
+    source varchar(255) not null,
+    code varchar(255) not null,
+    target varchar(500) not null,
+    paper varchar(255) not null,
+    pen varchar(255) not null
+);
+
+-- MySQL has no `create index if not exists`, and a table made before migrations is left as
+-- it was by `create table if not exists`, so each index is created only when it is missing.
+set @stm = (
+    select if(count(*) = 0, 'create index edge_t_source_code on edge_t (source, code)', 'do 0')
+    from information_schema.statistics
+    where table_schema = database() and table_name = 'edge_t' and index_name = 'edge_t_source_code'
+);
+prepare stm from @stm;
+execute stm;
+deallocate prepare stm;
+
+set @stm = (
+    select if(count(*) = 0, 'create index edge_t_target_code on edge_t (target, code)', 'do 0')
+    from information_schema.statistics
+    where table_schema = database() and table_name = 'edge_t' and index_name = 'edge_t_target_code'
+);
+prepare stm from @stm;
+execute stm;
+deallocate prepare stm;
+
+set @stm = (
+    select if(count(*) = 0, 'create index edge_t_paper on edge_t (paper)', 'do 0')
+    from information_schema.statistics
+    where table_schema = database() and table_name = 'edge_t' and index_name = 'edge_t_paper'
+);
+prepare stm from @stm;
+execute stm;
+deallocate prepare stm;
+
+set @stm = (
+    select if(count(*) = 0, 'create index edge_t_pen on edge_t (pen)', 'do 0')
+    from information_schema.statistics
+    where table_schema = database() and table_name = 'edge_t' and index_name = 'edge_t_pen'
+);
+prepare stm from @stm;
+execute stm;
+deallocate prepare stm;
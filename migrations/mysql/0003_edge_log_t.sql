@@ -0,0 +1,12 @@
+create table if not exists edge_log_t (
+    seq bigint not null auto_increment primary key,
+    op varchar(16) not null,
+    source varchar(255) not null,
+    code varchar(255) not null,
+    target varchar(500) not null,
+    paper varchar(255) not null,
+    pen varchar(255) not null,
+    created_at bigint not null,
+    index edge_log_t_paper (paper),
+    index edge_log_t_pen (pen)
+);
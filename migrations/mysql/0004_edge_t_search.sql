@@ -0,0 +1,3 @@
+alter table edge_t
+    add index edge_t_code_target (code, target),
+    add fulltext index edge_t_target_text (target);
@@ -0,0 +1,6 @@
+create table if not exists paper_trash_t (
+    paper varchar(255) not null primary key,
+    owner varchar(255) not null,
+    trashed_at bigint not null,
+    index paper_trash_t_owner (owner)
+);
@@ -0,0 +1,4 @@
+alter table edge_t
+    add column created_at bigint not null default 0,
+    add column deleted_at bigint;
+create index if not exists edge_t_deleted_at on edge_t (deleted_at);
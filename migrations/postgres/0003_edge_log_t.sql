@@ -0,0 +1,12 @@
+create table if not exists edge_log_t (
+    seq bigserial primary key,
+    op text not null,
+    source text not null,
+    code text not null,
+    target text not null,
+    paper text not null,
+    pen text not null,
+    created_at bigint not null
+);
+create index if not exists edge_log_t_paper on edge_log_t (paper);
+create index if not exists edge_log_t_pen on edge_log_t (pen);
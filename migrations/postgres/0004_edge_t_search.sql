@@ -0,0 +1,2 @@
+create index if not exists edge_t_code_target on edge_t (code, target text_pattern_ops);
+create index if not exists edge_t_target_text on edge_t using gin (to_tsvector('simple', target));
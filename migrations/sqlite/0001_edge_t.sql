@@ -0,0 +1,12 @@
+create table if not exists edge_t (
+    id integer primary key autoincrement,
+    source text not null,
+    code text not null,
+    target text not null,
+    paper text not null,
+    pen text not null
+);
+create index if not exists edge_t_source_code on edge_t (source, code);
+create index if not exists edge_t_target_code on edge_t (target, code);
+create index if not exists edge_t_paper on edge_t (paper);
+create index if not exists edge_t_pen on edge_t (pen);
@@ -0,0 +1,3 @@
+alter table edge_t add column created_at integer not null default 0;
+alter table edge_t add column deleted_at integer;
+create index if not exists edge_t_deleted_at on edge_t (deleted_at);
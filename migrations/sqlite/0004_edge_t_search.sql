@@ -0,0 +1 @@
+create index if not exists edge_t_code_target on edge_t (code, target);
@@ -0,0 +1,6 @@
+create table if not exists paper_trash_t (
+    paper text not null primary key,
+    owner text not null,
+    trashed_at integer not null
+);
+create index if not exists paper_trash_t_owner on paper_trash_t (owner);
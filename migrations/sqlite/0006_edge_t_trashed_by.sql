@@ -0,0 +1,8 @@
+alter table edge_t add column trashed_by text;
+create index if not exists edge_t_trashed_by on edge_t (trashed_by);
+update edge_t set trashed_by = (
+    select t.paper from paper_trash_t t
+    where t.trashed_at = edge_t.deleted_at
+    and (edge_t.paper = t.paper or edge_t.source = t.paper or (edge_t.code = 'paper' and edge_t.target = t.paper))
+    limit 1
+) where deleted_at in (select trashed_at from paper_trash_t);
@@ -1,25 +1,622 @@
-use std::{future, io, pin::Pin, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    future, io,
+    pin::Pin,
+    sync::Arc,
+};
 
 use edge_lib::{
     data::{AsDataManager, Auth},
     util::Path,
 };
-use sqlx::{MySql, Pool};
+use sqlx::{any::AnyPoolOptions, Any, AnyConnection, Executor, Pool, Transaction};
+use tokio::{
+    sync::{mpsc, Mutex, MutexGuard},
+    time,
+};
 
+mod cache;
 mod dao;
+mod exchange;
+mod gc;
+mod graph;
+mod regexp;
+mod replica;
+
+pub use cache::{CacheDataManager, CacheStats, DEFAULT_CACHE_SIZE};
+pub use dao::{
+    now, Aggregate, Change, Dialect, Filter, FindMode, FindQuery, Order, Page, PageQuery, Pair,
+    Predicate, Trashed, OP_DELETE, OP_INSERT, WILDCARD,
+};
+pub use exchange::{parse_jsonl, parse_ntriples, to_jsonl, to_text, Format, Record};
+pub use gc::{Gc, GcReport, DEFAULT_GC_BATCH_SIZE};
+pub use graph::{Hop, Search, Subgraph, Visit, DEFAULT_MAX_DEPTH, DEFAULT_MAX_FAN_OUT};
+
+type TxSlot = Option<Transaction<'static, Any>>;
+
+/// Targets or roots written by one statement unless configured otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Evaluates `$read` with `$conn` bound to a connection for a read of `$this` outside the
+/// transaction.
+///
+/// The read goes to a replica when one is healthy. When the connection to it fails the
+/// read is run again on the primary and the replica is left out for a while. Other errors,
+/// like a bad query, are returned as they would fail the same way on the primary.
+macro_rules! read {
+    ($this:expr, $when:expr, |$conn:ident| $read:expr) => {{
+        let mut rs = None;
+        if let Some((i, mut conn)) = $this.replica_set.acquire().await {
+            let $conn = &mut *conn;
+            match $read.await {
+                Err(e) if replica::is_lost(&e) => {
+                    log::warn!("{e}\nwhen {} on replica {i}", $when);
+                    $this.replica_set.set_down(i);
+                }
+                rs1 => rs = Some(rs1),
+            }
+        }
+        match rs {
+            Some(rs) => rs,
+            None => {
+                let mut conn = $this
+                    .pool
+                    .acquire()
+                    .await
+                    .map_err(|e| io::Error::other(format!("{e}\nwhen {}", $when)))?;
+                let $conn = &mut *conn;
+                $read.await
+            }
+        }
+    }};
+}
 
 #[derive(Clone)]
 pub struct DbDataManager {
     auth: Auth,
-    pool: Pool<MySql>,
+    pool: Pool<Any>,
+    /// Replicas of `pool` taking the reads of the graph made outside a transaction.
+    replica_set: Arc<replica::ReplicaSet>,
+    dialect: Dialect,
+    /// Transaction opened by the first write and closed by `commit`.
+    /// Dropping it without commit rolls back.
+    tx: Arc<Mutex<TxSlot>>,
+    batch_size: usize,
+    /// Reads see the graph as it was at this time, in milliseconds since the unix epoch.
+    as_of: Option<i64>,
 }
 
 impl DbDataManager {
-    pub fn new(global: Pool<MySql>, auth: Auth) -> Self {
+    pub fn new(global: Pool<Any>, auth: Auth) -> Self {
+        let dialect = Dialect::from_url(global.connect_options().database_url.as_str());
         Self {
             auth,
             pool: global,
+            replica_set: Arc::new(replica::ReplicaSet::default()),
+            dialect,
+            tx: Arc::new(Mutex::new(None)),
+            batch_size: DEFAULT_BATCH_SIZE,
+            as_of: None,
+        }
+    }
+
+    /// Sets how many rows a single insert or delete may carry.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sends the reads of the graph made outside a transaction to these replicas of the
+    /// database in turn, falling back to the primary while none is healthy.
+    pub fn with_read_pool_v(mut self, read_pool_v: Vec<Pool<Any>>) -> Self {
+        self.replica_set = Arc::new(replica::ReplicaSet::new(read_pool_v));
+        self
+    }
+
+    /// Returns this manager reading from the primary only, for reads that must see the
+    /// latest commit.
+    pub fn primary(&self) -> Self {
+        Self {
+            replica_set: Arc::new(replica::ReplicaSet::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Brings the schema up to date and returns the applied version.
+    pub async fn migrate(&self) -> io::Result<i64> {
+        dao::migrate(self.pool.clone(), self.dialect).await
+    }
+
+    /// Returns a manager for `auth` sharing the pool but not the transaction.
+    pub fn with_auth(&self, auth: Auth) -> Self {
+        Self {
+            auth,
+            pool: self.pool.clone(),
+            replica_set: self.replica_set.clone(),
+            dialect: self.dialect,
+            tx: Arc::new(Mutex::new(None)),
+            batch_size: self.batch_size,
+            as_of: self.as_of,
+        }
+    }
+
+    /// Returns a read-only manager seeing what `auth` could see at `time`.
+    pub fn divide_as_of(&self, auth: Auth, time: i64) -> Arc<dyn AsDataManager> {
+        Arc::new(Self {
+            as_of: Some(time),
+            ..self.with_auth(auth)
+        })
+    }
+
+    /// Counts the targets of `path` in the database instead of fetching them.
+    pub async fn count(&self, path: &Path) -> io::Result<u64> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::count(tx, self.dialect, &self.auth, self.as_of, path).await;
+        }
+        drop(tx);
+        read!(self, "count", |conn| dao::count(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            path
+        ))
+    }
+
+    /// Tells whether `value` is a target of `path` without fetching the others.
+    pub async fn contains(&self, path: &Path, value: &str) -> io::Result<bool> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::contains(tx, self.dialect, &self.auth, self.as_of, path, value).await;
         }
+        drop(tx);
+        read!(self, "contains", |conn| dao::contains(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            path,
+            value
+        ))
+    }
+
+    /// Reads the page of the targets of `path` that `query` selects.
+    ///
+    /// Targets come in the order of the id of their last edge. Pass the returned cursor to
+    /// continue after the last target of a page without counting an offset again.
+    pub async fn get_page(&self, path: &Path, query: &PageQuery) -> io::Result<Page> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::get_page(tx, self.dialect, &self.auth, self.as_of, path, query).await;
+        }
+        drop(tx);
+        read!(self, "get_page", |conn| dao::get_page(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            path,
+            query
+        ))
+    }
+
+    /// Reads a page of `(code, target)` pairs of `path`, typically one ending in a wildcard
+    /// step such as `node->*`.
+    pub async fn get_pair_page(&self, path: &Path, query: &PageQuery) -> io::Result<Page<Pair>> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::get_pair_page(tx, self.dialect, &self.auth, self.as_of, path, query).await;
+        }
+        drop(tx);
+        read!(self, "get_pair_page", |conn| dao::get_pair_page(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            path,
+            query
+        ))
+    }
+
+    /// Reads a page of the edges whose targets match `find`, e.g. users by email prefix.
+    pub async fn find(&self, find: &FindQuery, query: &PageQuery) -> io::Result<Page<Hop>> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::find(tx, self.dialect, &self.auth, self.as_of, find, query).await;
+        }
+        drop(tx);
+        read!(self, "find", |conn| dao::find(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            find,
+            query
+        ))
+    }
+
+    /// Counts and summarizes the targets of `path`, per node reached by its first `group_by`
+    /// steps when given.
+    pub async fn aggregate(
+        &self,
+        path: &Path,
+        group_by: Option<usize>,
+    ) -> io::Result<Vec<Aggregate>> {
+        let mut tx = self.tx.lock().await;
+        if let Some(tx) = tx.as_mut() {
+            return dao::aggregate(tx, self.dialect, &self.auth, self.as_of, path, group_by).await;
+        }
+        drop(tx);
+        read!(self, "aggregate", |conn| dao::aggregate(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            path,
+            group_by
+        ))
+    }
+
+    /// Lists the nodes reachable from `from` within the limits of `search`, nearest first.
+    pub async fn bfs(&self, from: &str, search: &Search) -> io::Result<Vec<Visit>> {
+        Ok(self.walk(&[from.to_string()], None, search).await?.visit_v)
+    }
+
+    /// Returns the edges of a shortest chain from `from` to `to`, `None` when there is none
+    /// within the limits of `search`.
+    pub async fn shortest_path(
+        &self,
+        from: &str,
+        to: &str,
+        search: &Search,
+    ) -> io::Result<Option<Vec<Hop>>> {
+        Ok(self
+            .walk(&[from.to_string()], Some(to), search)
+            .await?
+            .path_to(to))
+    }
+
+    /// Returns the nodes around `center` within the limits of `search` and the edges walked
+    /// to find them.
+    pub async fn neighborhood(&self, center: &str, search: &Search) -> io::Result<Subgraph> {
+        let walk = self.walk(&[center.to_string()], None, search).await?;
+        Ok(Subgraph {
+            node_v: walk.visit_v,
+            edge_v: walk.edge_v,
+        })
+    }
+
+    /// Returns the subgraph found from the targets of `path` within the limits of `search`.
+    pub async fn path_subgraph(&self, path: &Path, search: &Search) -> io::Result<Subgraph> {
+        read!(self, "path_subgraph", |conn| async {
+            let root_v = dao::get(conn, self.dialect, &self.auth, self.as_of, path).await?;
+            let walk = graph::bfs(
+                conn,
+                self.dialect,
+                &self.auth,
+                self.as_of,
+                &root_v,
+                None,
+                search,
+            )
+            .await?;
+            io::Result::Ok(Subgraph {
+                node_v: walk.visit_v,
+                edge_v: walk.edge_v,
+            })
+        })
+    }
+
+    pub async fn is_reachable(&self, from: &str, to: &str, search: &Search) -> io::Result<bool> {
+        Ok(self.shortest_path(from, to, search).await?.is_some())
+    }
+
+    async fn walk(
+        &self,
+        from_v: &[String],
+        to: Option<&str>,
+        search: &Search,
+    ) -> io::Result<graph::Walk> {
+        read!(self, "walk", |conn| graph::bfs(
+            conn,
+            self.dialect,
+            &self.auth,
+            self.as_of,
+            from_v,
+            to,
+            search
+        ))
+    }
+
+    /// Streams the committed targets of `path` in the order of `get`.
+    ///
+    /// Rows are read on a connection of their own while the receiver keeps up, so the
+    /// targets are never held in memory all at once. They are read on the primary, as rows
+    /// already sent could not be read again from it when a replica failed midway.
+    pub fn stream(&self, path: &Path) -> mpsc::Receiver<io::Result<String>> {
+        let (sender, receiver) = mpsc::channel(self.batch_size.max(1));
+        let this = self.clone();
+        let path = path.clone();
+        tokio::spawn(async move {
+            let rs = async {
+                let mut conn = this
+                    .pool
+                    .acquire()
+                    .await
+                    .map_err(|e| io::Error::other(format!("{e}\nwhen stream")))?;
+                dao::stream(
+                    &mut conn,
+                    this.dialect,
+                    &this.auth,
+                    this.as_of,
+                    &path,
+                    &sender,
+                )
+                .await
+            }
+            .await;
+            if let Err(e) = rs {
+                let _ = sender.send(Err(e)).await;
+            }
+        });
+        receiver
+    }
+
+    /// Removes the history of edges deleted before `before`.
+    pub async fn purge_history(&self, before: i64) -> io::Result<u64> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen purge_history")))?;
+        dao::purge_history(&mut conn, self.dialect, before).await
+    }
+
+    /// Deletes the live edges of the nodes that neither `root` nor a paper reaches, as `gc`
+    /// says.
+    ///
+    /// Orphans are deleted in batches, each committed on its own.
+    pub async fn gc(&self, gc: &Gc) -> io::Result<GcReport> {
+        let paper = gc.paper.as_deref();
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen gc")))?;
+        let marked = gc::mark(&mut conn, self.dialect, paper).await?;
+        let orphan_v = gc::orphan_v(&mut conn, self.dialect, paper, &marked).await?;
+        let mut report = GcReport {
+            marked: marked.len(),
+            orphan_v,
+            edge_cnt: 0,
+        };
+        if gc.dry_run {
+            report.edge_cnt =
+                gc::count_edge(&mut conn, self.dialect, paper, &report.orphan_v).await?;
+            return Ok(report);
+        }
+        drop(conn);
+        let orphan_set: HashSet<String> = report.orphan_v.iter().cloned().collect();
+        for (i, node_v) in report.orphan_v.chunks(gc.batch_size.max(1)).enumerate() {
+            if i > 0 {
+                time::sleep(gc.pause).await;
+            }
+            let mut tx = self
+                .pool
+                .begin()
+                .await
+                .map_err(|e| io::Error::other(format!("{e}\nwhen gc")))?;
+            report.edge_cnt += gc::sweep(&mut tx, self.dialect, paper, node_v, &orphan_set).await?;
+            tx.commit()
+                .await
+                .map_err(|e| io::Error::other(format!("{e}\nwhen gc")))?;
+        }
+        Ok(report)
+    }
+
+    /// Moves `paper` with all of its edges into the trash of `owner`.
+    ///
+    /// Like the other paper operations below, it ignores the auth of this manager and
+    /// commits on its own.
+    pub async fn trash_paper(&self, paper: &str, owner: &str) -> io::Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen trash_paper")))?;
+        dao::trash_paper(&mut tx, self.dialect, paper, owner).await?;
+        tx.commit()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen trash_paper")))
+    }
+
+    /// Brings `paper` back from the trash, returning `false` when it is not there.
+    pub async fn restore_paper(&self, paper: &str) -> io::Result<bool> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen restore_paper")))?;
+        let is_restored = dao::restore_paper(&mut tx, self.dialect, paper).await?;
+        tx.commit()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen restore_paper")))?;
+        Ok(is_restored)
+    }
+
+    /// Removes every edge of `paper` for good, whether it is in the trash or not.
+    pub async fn purge_paper(&self, paper: &str) -> io::Result<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen purge_paper")))?;
+        let cnt = dao::purge_paper(&mut tx, self.dialect, paper).await?;
+        tx.commit()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen purge_paper")))?;
+        Ok(cnt)
+    }
+
+    /// Purges the papers trashed before `before`, returning how many there were.
+    pub async fn purge_trash(&self, before: i64) -> io::Result<usize> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen purge_trash")))?;
+        let paper_v = dao::expired_trash(&mut conn, self.dialect, before).await?;
+        drop(conn);
+        for paper in &paper_v {
+            self.purge_paper(paper).await?;
+        }
+        Ok(paper_v.len())
+    }
+
+    /// Returns the owner of `paper` when it is in the trash.
+    pub async fn trash_owner(&self, paper: &str) -> io::Result<Option<String>> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen trash_owner")))?;
+        dao::trash_owner(&mut conn, self.dialect, paper).await
+    }
+
+    /// Lists the papers `owner` has in the trash, most recently trashed first.
+    pub async fn list_trash(&self, owner: &str) -> io::Result<Vec<Trashed>> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen list_trash")))?;
+        dao::list_trash(&mut conn, self.dialect, owner).await
+    }
+
+    /// Streams the live edges of `paper`, oldest first, whatever the auth of this manager.
+    pub fn export_paper(&self, paper: &str) -> mpsc::Receiver<io::Result<Record>> {
+        let (sender, receiver) = mpsc::channel(self.batch_size.max(1));
+        let this = self.clone();
+        let paper = paper.to_string();
+        tokio::spawn(async move {
+            let rs = async {
+                let mut conn = this
+                    .pool
+                    .acquire()
+                    .await
+                    .map_err(|e| io::Error::other(format!("{e}\nwhen export_paper")))?;
+                dao::export_paper(&mut conn, this.dialect, &paper, &sender).await
+            }
+            .await;
+            if let Err(e) = rs {
+                let _ = sender.send(Err(e)).await;
+            }
+        });
+        receiver
+    }
+
+    /// Imports `record_v` into `paper` under fresh node ids, returning the id each node of
+    /// the records got.
+    ///
+    /// `root` is kept, and `from`, the paper the records were exported from, becomes `paper`.
+    pub async fn import_paper(
+        &self,
+        paper: &str,
+        from: Option<&str>,
+        mut record_v: Vec<Record>,
+    ) -> io::Result<BTreeMap<String, String>> {
+        for record in &record_v {
+            dao::check_code(&record.code)?;
+        }
+        let id_map = exchange::remap(&mut record_v, from, paper);
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen import_paper")))?;
+        dao::insert_record(&mut tx, self.dialect, paper, &record_v, self.batch_size).await?;
+        tx.commit()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen import_paper")))?;
+        Ok(id_map)
+    }
+
+    /// Deletes the `code` edges from `source` to the targets of `target_v` in the
+    /// transaction, leaving its other `code` edges as they are.
+    pub async fn delete_target(
+        &self,
+        source: &str,
+        code: &str,
+        target_v: &[String],
+    ) -> io::Result<()> {
+        let mut tx = self.begin().await?;
+        let conn = &mut **tx.as_mut().unwrap();
+        dao::delete_target(
+            conn,
+            self.dialect,
+            &self.auth,
+            source,
+            code,
+            target_v,
+            self.batch_size,
+        )
+        .await
+    }
+
+    /// Returns at most `limit` committed changes after sequence number `seq`, oldest first.
+    ///
+    /// Pass the `seq` of the last change seen to follow the log.
+    pub async fn changes_since(&self, seq: i64, limit: usize) -> io::Result<Vec<Change>> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| io::Error::other(format!("{e}\nwhen changes_since")))?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        dao::changes_since(&mut conn, self.dialect, &self.auth, seq, limit).await
+    }
+
+    /// Returns statements selecting the roots of `path` to write `code` edges from.
+    ///
+    /// When writing `code` edges may change what `path` resolves to, its roots are resolved
+    /// once up front, so that a later batch does not start from the edges of an earlier one.
+    async fn gen_root_v(
+        &self,
+        conn: &mut AnyConnection,
+        path: &Path,
+        code: &str,
+    ) -> io::Result<Vec<dao::Stm>> {
+        if !path
+            .step_v
+            .iter()
+            .any(|s| [code, dao::WILDCARD].contains(&dao::base_code(&s.code)))
+        {
+            return Ok(vec![dao::gen_root_stm(&self.auth, None, path)]);
+        }
+        let root_v = dao::get(conn, self.dialect, &self.auth, None, path).await?;
+        let batch_size = self.batch_size.min(self.dialect.max_compound_select());
+        Ok(root_v
+            .chunks(batch_size.max(1))
+            .map(dao::Stm::values)
+            .collect())
+    }
+
+    /// Locks the transaction of this manager, beginning it when none is open.
+    async fn begin(&self) -> io::Result<MutexGuard<'_, TxSlot>> {
+        if self.as_of.is_some() {
+            return Err(io::Error::other("can not write into the past"));
+        }
+        let mut tx = self.tx.lock().await;
+        if tx.is_none() {
+            *tx = Some(
+                self.pool
+                    .begin()
+                    .await
+                    .map_err(|e| io::Error::other(format!("{e}\nwhen begin")))?,
+            );
+        }
+        Ok(tx)
     }
 }
 
@@ -29,14 +626,20 @@ impl AsDataManager for DbDataManager {
     }
 
     fn divide(&self, auth: Auth) -> Arc<dyn AsDataManager> {
-        Arc::new(Self {
-            auth,
-            pool: self.pool.clone(),
-        })
+        Arc::new(self.with_auth(auth))
     }
 
     fn commit(&self) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
-        Box::pin(future::ready(Ok(())))
+        let this = self.clone();
+        Box::pin(async move {
+            let tx = this.tx.lock().await.take();
+            if let Some(tx) = tx {
+                tx.commit()
+                    .await
+                    .map_err(|e| io::Error::other(format!("{e}\nwhen commit")))?;
+            }
+            Ok(())
+        })
     }
 
     fn append(
@@ -51,10 +654,23 @@ impl AsDataManager for DbDataManager {
         let mut path = path.clone();
         Box::pin(async move {
             let step = path.step_v.pop().unwrap();
-            let root_v = this.get(&path).await?;
-            for source in &root_v {
-                dao::insert_edge(this.pool.clone(), &this.auth, source, &step.code, &item_v)
-                    .await?;
+            if path.step_v.is_empty() && path.root.is_empty() {
+                return Ok(());
+            }
+            dao::check_code(&step.code)?;
+            let mut tx = this.begin().await?;
+            let conn = &mut **tx.as_mut().unwrap();
+            for root in &this.gen_root_v(conn, &path, &step.code).await? {
+                dao::insert_edge(
+                    conn,
+                    this.dialect,
+                    &this.auth,
+                    root,
+                    &step.code,
+                    &item_v,
+                    this.batch_size,
+                )
+                .await?;
             }
             Ok(())
         })
@@ -72,20 +688,29 @@ impl AsDataManager for DbDataManager {
         let mut path = path.clone();
         Box::pin(async move {
             let step = path.step_v.pop().unwrap();
-            let root_v = this.get(&path).await?;
-            for source in &root_v {
-                dao::delete_edge_with_source_code(
-                    this.pool.clone(),
+            if path.step_v.is_empty() && path.root.is_empty() {
+                return Ok(());
+            }
+            dao::check_code(&step.code)?;
+            let mut tx = this.begin().await?;
+            let conn = &mut **tx.as_mut().unwrap();
+            let root_v = this.gen_root_v(conn, &path, &step.code).await?;
+            for root in &root_v {
+                dao::delete_edge_with_source_code(conn, this.dialect, &this.auth, root, &step.code)
+                    .await?;
+            }
+            for root in &root_v {
+                dao::insert_edge(
+                    conn,
+                    this.dialect,
                     &this.auth,
-                    source,
+                    root,
                     &step.code,
+                    &item_v,
+                    this.batch_size,
                 )
                 .await?;
             }
-            for source in &root_v {
-                dao::insert_edge(this.pool.clone(), &this.auth, source, &step.code, &item_v)
-                    .await?;
-            }
             Ok(())
         })
     }
@@ -102,11 +727,1468 @@ impl AsDataManager for DbDataManager {
         }
         let this = self.clone();
         let path = path.clone();
-        Box::pin(async move { dao::get(this.pool.clone(), &this.auth, &path).await })
+        Box::pin(async move {
+            // Read own writes while a transaction is open.
+            let mut tx = this.tx.lock().await;
+            if let Some(tx) = tx.as_mut() {
+                return dao::get(tx, this.dialect, &this.auth, this.as_of, &path).await;
+            }
+            drop(tx);
+            read!(this, "get", |conn| dao::get(
+                conn,
+                this.dialect,
+                &this.auth,
+                this.as_of,
+                &path
+            ))
+        })
     }
 
     fn clear(&self) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
         let this = self.clone();
-        Box::pin(async move { dao::clear(this.pool, &this.auth).await })
+        Box::pin(async move {
+            let mut tx = this.begin().await?;
+            dao::clear(tx.as_mut().unwrap(), this.dialect, &this.auth).await
+        })
+    }
+}
+
+/// Installs the drivers `db_url` may pick, with a `regexp` function on SQLite.
+pub fn install_drivers() {
+    sqlx::any::install_default_drivers();
+    regexp::install();
+}
+
+/// Options of the pools a `DbDataManager` is made from.
+///
+/// MySQL fails a recursive step walking more than `cte_max_recursion_depth` levels, 1000 by
+/// default, so its connections lift that limit; the walk still ends once every edge of the
+/// code was walked.
+pub fn pool_options() -> AnyPoolOptions {
+    AnyPoolOptions::new().after_connect(|conn, _| {
+        Box::pin(async move {
+            if conn.backend_name() == "MySQL" {
+                conn.execute("set session cte_max_recursion_depth = 4294967295")
+                    .await?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Returns a manager printing as `root` into a fresh, migrated in-memory database.
+#[cfg(test)]
+pub(crate) async fn test_db() -> DbDataManager {
+    install_drivers();
+    let pool = pool_options()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let db = DbDataManager::new(pool, Auth::printer("root"));
+    db.migrate().await.unwrap();
+    db
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use edge_lib::{
+        data::{AsDataManager, Auth, MemDataManager},
+        util::Path,
+    };
+    use sqlx::any::AnyPoolOptions;
+    use tokio::time;
+
+    use super::{
+        install_drivers, parse_jsonl, test_db, to_jsonl, DbDataManager, Dialect, Filter, FindMode,
+        FindQuery, Gc, Hop, Order, PageQuery, Predicate, Record, Search, OP_DELETE, OP_INSERT,
+    };
+
+    #[tokio::test]
+    async fn test_sqlite() {
+        let dm = test_db().await;
+        let version = dm.migrate().await.unwrap();
+        assert!(version > 0);
+        assert_eq!(dm.migrate().await.unwrap(), version);
+        dm.append(
+            &Path::from_str("root->user"),
+            vec!["u1".to_string(), "u2".to_string()],
+        )
+        .await
+        .unwrap();
+        dm.set(&Path::from_str("root->user->name"), vec!["n".to_string()])
+            .await
+            .unwrap();
+        dm.set(&Path::from_str("u1->name"), vec!["n1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->user->name")).await.unwrap(),
+            vec!["n".to_string(), "n1".to_string()]
+        );
+        assert_eq!(
+            dm.get(&Path::from_str("n1<-name")).await.unwrap(),
+            vec!["u1".to_string()]
+        );
+        dm.commit().await.unwrap();
+
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        paper
+            .append(&Path::from_str("root->user"), vec!["u3".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            paper.get(&Path::from_str("root->user")).await.unwrap(),
+            vec!["u3".to_string()]
+        );
+        paper.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->user")).await.unwrap().len(),
+            3
+        );
+
+        paper.clear().await.unwrap();
+        paper.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->user")).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let dm = test_db().await;
+
+        let writer = dm.divide(Auth::printer("root"));
+        writer
+            .append(&Path::from_str("root->user"), vec!["u1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            writer.get(&Path::from_str("root->user")).await.unwrap(),
+            vec!["u1".to_string()]
+        );
+        drop(writer);
+        assert!(dm
+            .get(&Path::from_str("root->user"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let writer = dm.divide(Auth::printer("root"));
+        writer
+            .append(&Path::from_str("root->user"), vec!["u2".to_string()])
+            .await
+            .unwrap();
+        writer.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->user")).await.unwrap(),
+            vec!["u2".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hostile_auth() {
+        let dm = test_db().await;
+        dm.append(&Path::from_str("root->user"), vec!["u1".to_string()])
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+
+        let paper = "p' or '1' = '1";
+        let pen = "x'); delete from edge_t; --";
+        let hostile = dm.divide(Auth::writer(paper, pen));
+        assert!(hostile
+            .get(&Path::from_str("root->user"))
+            .await
+            .unwrap()
+            .is_empty());
+        hostile
+            .append(&Path::from_str("root->user"), vec!["u2".to_string()])
+            .await
+            .unwrap();
+        hostile
+            .set(&Path::from_str("u2->name"), vec![pen.to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            hostile
+                .get(&Path::from_str("root->user->name"))
+                .await
+                .unwrap(),
+            vec![pen.to_string()]
+        );
+        hostile.clear().await.unwrap();
+        hostile.commit().await.unwrap();
+
+        let printer = dm.divide(Auth::printer(pen));
+        printer.clear().await.unwrap();
+        printer.commit().await.unwrap();
+
+        assert_eq!(
+            dm.get(&Path::from_str("root->user")).await.unwrap(),
+            vec!["u1".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conformance() {
+        for auth in [Auth::printer("root"), Auth::writer("paper", "pen")] {
+            // One target per statement, so that every batch boundary is crossed.
+            conformance(test_db().await.with_batch_size(1), auth).await;
+        }
+    }
+
+    /// Runs the conformance suite on the Postgres database at `EDGE_TEST_PG_URL`, skipped
+    /// when it is not set.
+    ///
+    /// The database is a scratch one: its edges are deleted before each run.
+    #[tokio::test]
+    async fn test_conformance_postgres() {
+        let Ok(url) = std::env::var("EDGE_TEST_PG_URL") else {
+            return;
+        };
+        install_drivers();
+        let pool = AnyPoolOptions::new().connect(&url).await.unwrap();
+        let db = DbDataManager::new(pool, Auth::printer("root"));
+        assert_eq!(db.dialect, Dialect::Postgres);
+        db.migrate().await.unwrap();
+        for auth in [Auth::printer("root"), Auth::writer("paper", "pen")] {
+            sqlx::query("delete from edge_t")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            conformance(db.clone().with_batch_size(1), auth).await;
+        }
+    }
+
+    /// Builds the same graph as `auth` in `db` and in memory and expects equal answers for
+    /// every path.
+    async fn conformance(db: DbDataManager, auth: Auth) {
+        let dm_v: Vec<Arc<dyn AsDataManager>> = vec![
+            Arc::new(MemDataManager::new(auth.clone())),
+            Arc::new(db.with_auth(auth)),
+        ];
+        let edge_v = [
+            ("a->b", vec!["x1", "x2"]),
+            ("y1->c", vec!["x1"]),
+            ("y2->c", vec!["x1"]),
+            ("y3->c", vec!["x2"]),
+            ("y1->d", vec!["z1"]),
+            ("y2->d", vec!["z2"]),
+            ("y3->d", vec!["z3", "z1"]),
+            ("z1->b", vec!["x2"]),
+            ("root->a", vec!["root"]),
+            ("root->a->a", vec!["x1", "x2"]),
+        ];
+        for dm in &dm_v {
+            for (path, target_v) in &edge_v {
+                dm.append(
+                    &Path::from_str(path),
+                    target_v.iter().map(|s| s.to_string()).collect(),
+                )
+                .await
+                .unwrap();
+            }
+            dm.commit().await.unwrap();
+        }
+        let set_v = [
+            ("a->b<-c->d", vec!["w1", "w2"]),
+            ("x1<-c->c", vec!["x2"]),
+            ("z1->b->b", vec!["x1"]),
+            ("w1<-d->e", vec![]),
+            ("a->b->b", vec!["a"]),
+        ];
+        for dm in &dm_v {
+            for (path, target_v) in &set_v {
+                dm.set(
+                    &Path::from_str(path),
+                    target_v.iter().map(|s| s.to_string()).collect(),
+                )
+                .await
+                .unwrap();
+            }
+            dm.commit().await.unwrap();
+        }
+        let path_v = [
+            "a->b",
+            "x1<-c",
+            "a->b<-c",
+            "a->b<-c->d",
+            "a->b<-c->d->b",
+            "a->b<-b",
+            "z1<-d->c<-b",
+            "z1<-d<-c",
+            "z1->b<-c->d<-d",
+            "x2<-b<-b",
+            "nothing->b<-c",
+            "a->b<-c->d",
+            "y1->c",
+            "x2<-c",
+            "a->b->b",
+            "x1->b",
+            "root->a",
+            "root->a->a",
+            "x1->a",
+        ];
+        for path in path_v {
+            let mut expect = dm_v[0].get(&Path::from_str(path)).await.unwrap();
+            expect.sort();
+            for dm in &dm_v[1..] {
+                let mut rs = dm.get(&Path::from_str(path)).await.unwrap();
+                rs.sort();
+                assert_eq!(rs, expect, "{path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_insert() {
+        let dm = test_db().await;
+        let item_v: Vec<String> = (0..300000).map(|i| format!("{i}")).collect();
+        dm.append(&Path::from_str("root->item"), item_v.clone())
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        assert_eq!(dm.get(&Path::from_str("root->item")).await.unwrap(), item_v);
+
+        dm.set(&Path::from_str("root->item"), item_v[..200000].to_vec())
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->item")).await.unwrap(),
+            item_v[..200000].to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_size() {
+        let dm = test_db().await.with_batch_size(7);
+        let user_v: Vec<String> = (0..20).map(|i| format!("u{i}")).collect();
+        let item_v: Vec<String> = (0..30).map(|i| format!("{i}")).collect();
+        dm.append(&Path::from_str("root->user"), user_v.clone())
+            .await
+            .unwrap();
+        dm.append(&Path::from_str("root->user->item"), item_v.clone())
+            .await
+            .unwrap();
+        // `user` in both the parent and the last step pins the roots in batches.
+        dm.set(&Path::from_str("root->user->user"), item_v.clone())
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        for user in &user_v {
+            assert_eq!(
+                dm.get(&Path::from_str(&format!("{user}->item")))
+                    .await
+                    .unwrap(),
+                item_v
+            );
+            assert_eq!(
+                dm.get(&Path::from_str(&format!("{user}->user")))
+                    .await
+                    .unwrap(),
+                item_v
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_as_of() {
+        let dm = test_db().await;
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        paper
+            .set(&Path::from_str("a->b"), vec!["1".to_string()])
+            .await
+            .unwrap();
+        paper.commit().await.unwrap();
+        time::sleep(Duration::from_millis(5)).await;
+        let t1 = super::now();
+        time::sleep(Duration::from_millis(5)).await;
+        paper
+            .set(&Path::from_str("a->b"), vec!["2".to_string()])
+            .await
+            .unwrap();
+        paper.commit().await.unwrap();
+
+        let past = dm.divide_as_of(Auth::writer("paper", "pen"), t1);
+        assert_eq!(
+            past.get(&Path::from_str("a->b")).await.unwrap(),
+            vec!["1".to_string()]
+        );
+        assert!(past
+            .set(&Path::from_str("a->b"), vec!["3".to_string()])
+            .await
+            .is_err());
+        let other = dm.divide_as_of(Auth::writer("other", "pen"), t1);
+        assert!(other.get(&Path::from_str("a->b")).await.unwrap().is_empty());
+        assert_eq!(
+            paper.get(&Path::from_str("a->b")).await.unwrap(),
+            vec!["2".to_string()]
+        );
+
+        assert_eq!(dm.purge_history(super::now()).await.unwrap(), 1);
+        assert!(past.get(&Path::from_str("a->b")).await.unwrap().is_empty());
+        assert_eq!(
+            paper.get(&Path::from_str("a->b")).await.unwrap(),
+            vec!["2".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changes_since() {
+        let dm = test_db().await;
+        let before = super::now();
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        paper
+            .append(
+                &Path::from_str("a->b"),
+                vec!["1".to_string(), "2".to_string()],
+            )
+            .await
+            .unwrap();
+        paper
+            .set(&Path::from_str("a->b"), vec!["3".to_string()])
+            .await
+            .unwrap();
+        paper.commit().await.unwrap();
+        let other = dm.divide(Auth::writer("other", "pen"));
+        other
+            .append(&Path::from_str("c->d"), vec!["4".to_string()])
+            .await
+            .unwrap();
+        other.clear().await.unwrap();
+        other.commit().await.unwrap();
+
+        let change_v = dm.changes_since(0, 100).await.unwrap();
+        let entry_v: Vec<(&str, &str, &str, &str)> = change_v
+            .iter()
+            .map(|c| {
+                (
+                    c.op.as_str(),
+                    c.source.as_str(),
+                    c.code.as_str(),
+                    c.target.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entry_v,
+            vec![
+                (OP_INSERT, "a", "b", "1"),
+                (OP_INSERT, "a", "b", "2"),
+                (OP_DELETE, "a", "b", "1"),
+                (OP_DELETE, "a", "b", "2"),
+                (OP_INSERT, "a", "b", "3"),
+                (OP_INSERT, "c", "d", "4"),
+                (OP_DELETE, "c", "d", "4"),
+            ]
+        );
+        assert!(change_v.windows(2).all(|w| w[0].seq < w[1].seq));
+        let after = super::now();
+        assert!(change_v
+            .iter()
+            .all(|c| c.created_at >= before && c.created_at <= after));
+
+        let tail = dm.changes_since(change_v[4].seq, 1).await.unwrap();
+        assert_eq!(tail, vec![change_v[5].clone()]);
+
+        let paper = DbDataManager::new(dm.pool.clone(), Auth::writer("paper", "pen"));
+        let change_v = paper.changes_since(0, 100).await.unwrap();
+        assert_eq!(change_v.len(), 5);
+        assert!(change_v.iter().all(|c| c.paper == "paper"));
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let dm = test_db().await;
+        let item_v: Vec<String> = (0..25).map(|i| format!("{i}")).collect();
+        dm.append(&Path::from_str("root->item"), item_v.clone())
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+
+        let path = Path::from_str("root->item");
+        let page = dm
+            .get_page(
+                &path,
+                &PageQuery {
+                    cursor: None,
+                    offset: 20,
+                    limit: 10,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.item_v, item_v[20..].to_vec());
+        assert_eq!(page.cursor, None);
+
+        let mut rs = Vec::new();
+        let mut query = PageQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        loop {
+            let page = dm.get_page(&path, &query).await.unwrap();
+            rs.extend(page.item_v);
+            match page.cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(rs, item_v);
+
+        // Both `r->a` edges lead to the same `n->b` edge.
+        dm.append(
+            &Path::from_str("r->a"),
+            vec!["n".to_string(), "n".to_string()],
+        )
+        .await
+        .unwrap();
+        dm.append(&Path::from_str("n->b"), vec!["x".to_string()])
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        let joined = Path::from_str("r->a->b");
+        let query = PageQuery {
+            limit: 1,
+            ..Default::default()
+        };
+        let page = dm.get_page(&joined, &query).await.unwrap();
+        assert_eq!(page.item_v, vec!["x"]);
+        assert_eq!(page.cursor, None);
+        let query = PageQuery { offset: 1, ..query };
+        assert_eq!(
+            dm.get_page(&joined, &query).await.unwrap().item_v,
+            vec!["x"]
+        );
+        let query = PageQuery {
+            cursor: Some(0),
+            ..query
+        };
+        assert!(dm.get_page(&joined, &query).await.is_err());
+
+        let mut receiver = dm.stream(&path);
+        let mut rs = Vec::new();
+        while let Some(item) = receiver.recv().await {
+            rs.push(item.unwrap());
+        }
+        assert_eq!(rs, item_v);
+
+        let paper = dm.with_auth(Auth::writer("paper", "pen"));
+        assert!(paper
+            .get_page(&path, &PageQuery::default())
+            .await
+            .unwrap()
+            .item_v
+            .is_empty());
+        assert!(paper.stream(&path).recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_order_filter() {
+        let dm = test_db().await;
+        let edge_v = [
+            ("root->shop", vec!["s1", "s2"]),
+            ("s1->price", vec!["10", "9", "x", "-1"]),
+            ("s2->price", vec!["5", "a", "1*"]),
+        ];
+        for (path, target_v) in &edge_v {
+            dm.append(
+                &Path::from_str(path),
+                target_v.iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        }
+        dm.commit().await.unwrap();
+
+        let path = Path::from_str("root->shop->price");
+        let get = |order: Order, filter_v: Vec<Filter>| {
+            let dm = dm.clone();
+            let path = path.clone();
+            async move {
+                dm.get_page(
+                    &path,
+                    &PageQuery {
+                        order,
+                        filter_v,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map(|page| page.item_v)
+            }
+        };
+        let filter = |step: Option<usize>, predicate: Predicate| Filter { step, predicate };
+
+        assert_eq!(
+            get(Order::Numeric, vec![]).await.unwrap(),
+            vec!["-1", "5", "9", "10", "1*", "a", "x"]
+        );
+        assert_eq!(
+            get(Order::NumericDesc, vec![]).await.unwrap(),
+            vec!["10", "9", "5", "-1", "1*", "a", "x"]
+        );
+        assert_eq!(
+            get(Order::Lexical, vec![]).await.unwrap(),
+            vec!["-1", "1*", "10", "5", "9", "a", "x"]
+        );
+        assert_eq!(
+            get(Order::LexicalDesc, vec![]).await.unwrap(),
+            vec!["x", "a", "9", "5", "10", "1*", "-1"]
+        );
+        let eq = Predicate::Eq {
+            value: "s2".to_string(),
+        };
+        assert_eq!(
+            get(Order::Id, vec![filter(Some(1), eq)]).await.unwrap(),
+            vec!["5", "a", "1*"]
+        );
+        let prefix = |value: &str| Predicate::Prefix {
+            value: value.to_string(),
+        };
+        assert_eq!(
+            get(Order::Id, vec![filter(None, prefix("1"))])
+                .await
+                .unwrap(),
+            vec!["10", "1*"]
+        );
+        assert_eq!(
+            get(Order::Id, vec![filter(None, prefix("1*"))])
+                .await
+                .unwrap(),
+            vec!["1*"]
+        );
+        let range = Predicate::Range {
+            from: Some("5".to_string()),
+            to: Some("a".to_string()),
+        };
+        assert_eq!(
+            get(Order::Id, vec![filter(None, range)]).await.unwrap(),
+            vec!["9", "5"]
+        );
+        let range = Predicate::NumericRange {
+            from: Some(0.0),
+            to: Some(10.0),
+        };
+        assert_eq!(
+            get(Order::Numeric, vec![filter(None, range)])
+                .await
+                .unwrap(),
+            vec!["5", "9"]
+        );
+        let regex = |pattern: &str| Predicate::Regex {
+            pattern: pattern.to_string(),
+        };
+        assert_eq!(
+            get(Order::Id, vec![filter(None, regex("^[0-9]+$"))])
+                .await
+                .unwrap(),
+            vec!["10", "9", "5"]
+        );
+        assert_eq!(
+            get(Order::Lexical, vec![filter(Some(1), regex("2$"))])
+                .await
+                .unwrap(),
+            vec!["1*", "5", "a"]
+        );
+        assert!(get(Order::Id, vec![filter(None, regex("("))])
+            .await
+            .is_err());
+        let eq = Predicate::Eq {
+            value: "s2".to_string(),
+        };
+        assert!(get(Order::Id, vec![filter(Some(3), eq)]).await.is_err());
+        assert!(dm
+            .get_page(
+                &path,
+                &PageQuery {
+                    cursor: Some(0),
+                    order: Order::Lexical,
+                    ..Default::default()
+                },
+            )
+            .await
+            .is_err());
+
+        let page = dm
+            .get_pair_page(
+                &path,
+                &PageQuery {
+                    limit: 2,
+                    order: Order::LexicalDesc,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let pair_v: Vec<(&str, &str)> = page
+            .item_v
+            .iter()
+            .map(|pair| (pair.code.as_str(), pair.target.as_str()))
+            .collect();
+        assert_eq!(pair_v, vec![("price", "x"), ("price", "a")]);
+        assert_eq!(page.cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_find() {
+        let dm = test_db().await;
+        let edge_v = [
+            ("u1->email", "ann@a.com"),
+            ("u2->email", "bob@b.com"),
+            ("u3->email", "Ann_x@c.com"),
+            ("p1->name", "ann's paper"),
+        ];
+        for (path, target) in &edge_v {
+            dm.append(&Path::from_str(path), vec![target.to_string()])
+                .await
+                .unwrap();
+        }
+        dm.commit().await.unwrap();
+
+        let find = |code: &str, text: &str, mode: FindMode| FindQuery {
+            code: code.to_string(),
+            text: text.to_string(),
+            mode,
+        };
+        let source_v = |page: super::Page<Hop>| -> Vec<String> {
+            page.item_v.into_iter().map(|hop| hop.source).collect()
+        };
+        let query = PageQuery::default();
+
+        let page = dm
+            .find(&find("email", "ann", FindMode::Prefix), &query)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.item_v,
+            vec![Hop {
+                source: "u1".to_string(),
+                code: "email".to_string(),
+                target: "ann@a.com".to_string(),
+            }]
+        );
+        assert!(dm
+            .find(&find("email", "a%", FindMode::Prefix), &query)
+            .await
+            .unwrap()
+            .item_v
+            .is_empty());
+        assert_eq!(
+            source_v(
+                dm.find(&find("email", "@", FindMode::Contains), &query)
+                    .await
+                    .unwrap()
+            ),
+            vec!["u1", "u2", "u3"]
+        );
+        assert_eq!(
+            source_v(
+                dm.find(&find("*", "ann", FindMode::Text), &query)
+                    .await
+                    .unwrap()
+            ),
+            vec!["u1", "p1"]
+        );
+
+        let mut rs = Vec::new();
+        let mut query = PageQuery {
+            limit: 2,
+            ..Default::default()
+        };
+        loop {
+            let page = dm
+                .find(&find("email", ".com", FindMode::Contains), &query)
+                .await
+                .unwrap();
+            rs.extend(source_v(page.clone()));
+            match page.cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(rs, vec!["u1", "u2", "u3"]);
+
+        let paper = dm.with_auth(Auth::writer("paper", "pen"));
+        assert!(paper
+            .find(
+                &find("email", "ann", FindMode::Prefix),
+                &PageQuery::default()
+            )
+            .await
+            .unwrap()
+            .item_v
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trash() {
+        let dm = test_db().await;
+        let set = |dm: Arc<dyn AsDataManager>, path: &str, target_v: &[&str]| {
+            let path = Path::from_str(path);
+            let target_v = target_v.iter().map(|s| s.to_string()).collect();
+            async move {
+                dm.set(&path, target_v).await.unwrap();
+                dm.commit().await.unwrap();
+            }
+        };
+        let root: Arc<dyn AsDataManager> = Arc::new(dm.clone());
+        let paper = dm.divide(Auth::writer("p1", "pen"));
+        set(root.clone(), "owner->paper", &["p1"]).await;
+        set(root.clone(), "p1->writer", &["w"]).await;
+        set(paper.clone(), "a->b", &["c"]).await;
+        set(paper.clone(), "a->old", &["z"]).await;
+        set(paper.clone(), "a->old", &[]).await;
+        set(dm.divide(Auth::writer("p2", "pen")), "a->b", &["d"]).await;
+
+        dm.trash_paper("p1", "owner").await.unwrap();
+        // An edge deleted in the same millisecond as the trashing is not brought back.
+        sqlx::query(
+            "update edge_t set deleted_at = (select trashed_at from paper_trash_t) where code = 'old'",
+        )
+        .execute(&dm.pool)
+        .await
+        .unwrap();
+        let get = |dm: Arc<dyn AsDataManager>, path: &str| {
+            let path = Path::from_str(path);
+            async move { dm.get(&path).await.unwrap() }
+        };
+        assert!(get(paper.clone(), "a->b").await.is_empty());
+        assert!(get(root.clone(), "owner->paper").await.is_empty());
+        assert!(get(root.clone(), "p1->writer").await.is_empty());
+        assert_eq!(
+            get(dm.divide(Auth::writer("p2", "pen")), "a->b").await,
+            vec!["d".to_string()]
+        );
+        assert_eq!(
+            dm.trash_owner("p1").await.unwrap(),
+            Some("owner".to_string())
+        );
+        let trashed_v = dm.list_trash("owner").await.unwrap();
+        assert_eq!(trashed_v.len(), 1);
+        assert_eq!(trashed_v[0].paper, "p1");
+        assert!(trashed_v[0].trashed_at > super::now() - 60 * 1000);
+        // History retention spares papers in the trash.
+        dm.purge_history(super::now() + 1).await.unwrap();
+
+        assert!(dm.restore_paper("p1").await.unwrap());
+        assert!(!dm.restore_paper("p1").await.unwrap());
+        assert_eq!(get(paper.clone(), "a->b").await, vec!["c".to_string()]);
+        assert!(get(paper.clone(), "a->old").await.is_empty());
+        assert_eq!(
+            get(root.clone(), "owner->paper").await,
+            vec!["p1".to_string()]
+        );
+        assert_eq!(get(root.clone(), "p1->writer").await, vec!["w".to_string()]);
+        assert_eq!(dm.trash_owner("p1").await.unwrap(), None);
+
+        let t1 = super::now();
+        time::sleep(Duration::from_millis(5)).await;
+        dm.trash_paper("p1", "owner").await.unwrap();
+        assert_eq!(dm.purge_trash(t1).await.unwrap(), 0);
+        assert_eq!(dm.purge_trash(super::now() + 1).await.unwrap(), 1);
+        assert!(dm.list_trash("owner").await.unwrap().is_empty());
+        let past = dm.divide_as_of(Auth::writer("p1", "pen"), t1);
+        assert!(get(past, "a->b").await.is_empty());
+        assert!(!dm.restore_paper("p1").await.unwrap());
+        assert_eq!(
+            get(dm.divide(Auth::writer("p2", "pen")), "a->b").await,
+            vec!["d".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let dm = test_db().await;
+        let paper = dm.divide(Auth::writer("p1", "pen"));
+        for (path, target_v) in [
+            ("a->b", vec!["c", "x"]),
+            ("c->d", vec!["e"]),
+            ("a->name", vec!["alice"]),
+            ("root->x", vec!["a"]),
+            ("p1->note", vec!["hi"]),
+        ] {
+            paper
+                .append(
+                    &Path::from_str(path),
+                    target_v.iter().map(|s| s.to_string()).collect(),
+                )
+                .await
+                .unwrap();
+        }
+        paper.commit().await.unwrap();
+
+        let mut receiver = dm.export_paper("p1");
+        let mut jsonl = String::new();
+        while let Some(record) = receiver.recv().await {
+            jsonl.push_str(&to_jsonl(&record.unwrap()));
+        }
+        assert_eq!(jsonl.lines().count(), 6);
+        let record_v = parse_jsonl(&jsonl).unwrap();
+        assert_eq!(
+            record_v[0],
+            Record {
+                source: "a".to_string(),
+                code: "b".to_string(),
+                target: "c".to_string(),
+                pen: "pen".to_string(),
+            }
+        );
+        assert!(parse_jsonl("{\"source\": \"a\"}").is_err());
+
+        let id_map = dm
+            .import_paper("p2", Some("p1"), record_v.clone())
+            .await
+            .unwrap();
+        assert_eq!(id_map.len(), 4);
+        let (a, c) = (&id_map["a"], &id_map["c"]);
+        assert_ne!(a, "a");
+        assert_eq!(id_map["root"], "root");
+        assert_eq!(id_map["p1"], "p2");
+        let imported = dm.divide(Auth::writer("p2", "pen"));
+        let get = |path: String| {
+            let imported = imported.clone();
+            async move { imported.get(&Path::from_str(&path)).await.unwrap() }
+        };
+        assert_eq!(
+            get(format!("{a}->b")).await,
+            vec![c.clone(), "x".to_string()]
+        );
+        assert_eq!(get(format!("{c}->d")).await, vec!["e".to_string()]);
+        assert_eq!(get(format!("{a}->name")).await, vec!["alice".to_string()]);
+        assert!(get("a->b".to_string()).await.is_empty());
+        assert_eq!(get("root->x".to_string()).await, vec![a.clone()]);
+        assert_eq!(get("root->x->name".to_string()).await, vec!["alice"]);
+        assert_eq!(get("p2->note".to_string()).await, vec!["hi".to_string()]);
+
+        let again = dm.import_paper("p2", None, record_v.clone()).await.unwrap();
+        assert_ne!(again["a"], *a);
+
+        for code in ["*", "child*", "child*3"] {
+            let mut record_v = record_v.clone();
+            record_v[0].code = code.to_string();
+            assert!(dm.import_paper("p2", None, record_v).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_count_contains() {
+        let dm = test_db().await;
+        dm.append(
+            &Path::from_str("p->writer"),
+            vec!["w1".to_string(), "w2".to_string()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(dm.count(&Path::from_str("p->writer")).await.unwrap(), 2);
+        assert!(dm
+            .contains(&Path::from_str("p->writer"), "w2")
+            .await
+            .unwrap());
+        dm.commit().await.unwrap();
+
+        assert!(!dm
+            .contains(&Path::from_str("p->writer"), "w3")
+            .await
+            .unwrap());
+        assert_eq!(dm.count(&Path::from_str("w1<-writer")).await.unwrap(), 1);
+        assert_eq!(dm.count(&Path::from_str("p->manager")).await.unwrap(), 0);
+        assert_eq!(dm.count(&Path::from_str("p")).await.unwrap(), 1);
+        assert!(dm.contains(&Path::from_str("p"), "p").await.unwrap());
+
+        let paper = dm.with_auth(Auth::writer("paper", "pen"));
+        assert_eq!(paper.count(&Path::from_str("p->writer")).await.unwrap(), 0);
+        assert!(!paper
+            .contains(&Path::from_str("p->writer"), "w1")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_recursive() {
+        let dm = test_db().await;
+        let edge_v = [
+            ("root->child", vec!["a"]),
+            ("a->child", vec!["b", "c"]),
+            ("b->child", vec!["d"]),
+            ("d->child", vec!["a"]),
+            ("c->name", vec!["nc"]),
+            ("d->name", vec!["nd"]),
+        ];
+        for (path, target_v) in &edge_v {
+            dm.append(
+                &Path::from_str(path),
+                target_v.iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        }
+        dm.commit().await.unwrap();
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        paper
+            .append(&Path::from_str("x->child"), vec!["y".to_string()])
+            .await
+            .unwrap();
+        paper
+            .append(&Path::from_str("y->child"), vec!["z".to_string()])
+            .await
+            .unwrap();
+        paper
+            .append(&Path::from_str("d->child"), vec!["w".to_string()])
+            .await
+            .unwrap();
+        paper.commit().await.unwrap();
+
+        let get = |dm: Arc<dyn AsDataManager>, path: &'static str| async move {
+            let mut rs = dm.get(&Path::from_str(path)).await.unwrap();
+            rs.sort();
+            rs
+        };
+        let root: Arc<dyn AsDataManager> = Arc::new(dm.clone());
+        assert_eq!(
+            get(root.clone(), "root->child*").await,
+            vec!["a", "b", "c", "d", "w"]
+        );
+        assert_eq!(
+            get(root.clone(), "root->child*2").await,
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(get(root.clone(), "root->child*1").await, vec!["a"]);
+        assert_eq!(
+            get(root.clone(), "d<-child*").await,
+            vec!["a", "b", "d", "root"]
+        );
+        assert_eq!(
+            get(root.clone(), "root->child*->name").await,
+            vec!["nc", "nd"]
+        );
+        assert_eq!(
+            get(root.clone(), "a->child->child*").await,
+            vec!["a", "b", "c", "d", "w"]
+        );
+        assert_eq!(
+            get(root.clone(), "nd<-name<-child*<-child*").await,
+            vec!["a", "b", "d", "root"]
+        );
+        assert_eq!(dm.count(&Path::from_str("root->child*")).await.unwrap(), 5);
+
+        // A node comes in the order of the edges walked to it, not of other edges to it.
+        for (path, target) in [("q->next", "m2"), ("root->next", "m1"), ("m1->next", "m2")] {
+            dm.append(&Path::from_str(path), vec![target.to_string()])
+                .await
+                .unwrap();
+        }
+        dm.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&Path::from_str("root->next*")).await.unwrap(),
+            vec!["m1", "m2"]
+        );
+
+        // Auth filters every step of the closure.
+        assert_eq!(get(paper.clone(), "x->child*").await, vec!["y", "z"]);
+        assert!(get(paper.clone(), "root->child*").await.is_empty());
+        assert_eq!(get(paper.clone(), "d->child*").await, vec!["w"]);
+
+        assert!(dm
+            .append(&Path::from_str("root->child*"), vec!["e".to_string()])
+            .await
+            .is_err());
+        assert!(dm
+            .set(&Path::from_str("root->child*3"), vec!["e".to_string()])
+            .await
+            .is_err());
+        dm.set(
+            &Path::from_str("root->child*->child"),
+            vec!["e".to_string()],
+        )
+        .await
+        .unwrap();
+        dm.commit().await.unwrap();
+        // Every node of the closure now has `e` as its only child.
+        assert_eq!(get(root.clone(), "root->child*").await, vec!["a", "e"]);
+        assert_eq!(get(root.clone(), "b->child").await, vec!["e"]);
+    }
+
+    #[tokio::test]
+    async fn test_graph() {
+        let dm = test_db().await;
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        let edge_v = [
+            ("a->x", vec!["b", "e"]),
+            ("b->x", vec!["c"]),
+            ("c->x", vec!["d"]),
+            ("a->y", vec!["d"]),
+            ("d->x", vec!["a"]),
+        ];
+        for (path, target_v) in &edge_v {
+            paper
+                .append(
+                    &Path::from_str(path),
+                    target_v.iter().map(|s| s.to_string()).collect(),
+                )
+                .await
+                .unwrap();
+        }
+        paper.commit().await.unwrap();
+        let hop = |source: &str, code: &str, target: &str| Hop {
+            source: source.to_string(),
+            code: code.to_string(),
+            target: target.to_string(),
+        };
+        let x = Search {
+            code_v: vec!["x".to_string()],
+            ..Default::default()
+        };
+
+        let paper = dm.with_auth(Auth::writer("paper", "pen"));
+        assert_eq!(
+            paper
+                .shortest_path("a", "d", &Search::default())
+                .await
+                .unwrap(),
+            Some(vec![hop("a", "y", "d")])
+        );
+        assert_eq!(
+            paper.shortest_path("a", "d", &x).await.unwrap(),
+            Some(vec![
+                hop("a", "x", "b"),
+                hop("b", "x", "c"),
+                hop("c", "x", "d")
+            ])
+        );
+        let shallow = Search {
+            max_depth: 2,
+            ..x.clone()
+        };
+        assert!(!paper.is_reachable("a", "d", &shallow).await.unwrap());
+        assert_eq!(
+            paper.shortest_path("a", "a", &x).await.unwrap(),
+            Some(vec![])
+        );
+
+        let back = Search {
+            arrow: "<-".to_string(),
+            ..x.clone()
+        };
+        assert_eq!(
+            paper.shortest_path("d", "b", &back).await.unwrap(),
+            Some(vec![hop("c", "x", "d"), hop("b", "x", "c")])
+        );
+
+        let narrow = Search {
+            max_fan_out: 1,
+            ..x.clone()
+        };
+        let visit_v = paper.bfs("a", &narrow).await.unwrap();
+        let visit_v: Vec<(&str, usize)> = visit_v
+            .iter()
+            .map(|visit| (visit.node.as_str(), visit.depth))
+            .collect();
+        assert_eq!(visit_v, vec![("a", 0), ("b", 1), ("c", 2), ("d", 3)]);
+        assert!(paper.is_reachable("a", "e", &x).await.unwrap());
+        assert!(!paper.is_reachable("a", "e", &narrow).await.unwrap());
+        let bad = Search {
+            arrow: "=>".to_string(),
+            ..x.clone()
+        };
+        assert!(paper.bfs("a", &bad).await.is_err());
+
+        let subgraph = paper
+            .path_subgraph(
+                &Path::from_str("a->x"),
+                &Search {
+                    max_depth: 1,
+                    ..x.clone()
+                },
+            )
+            .await
+            .unwrap();
+        let node_v: Vec<(&str, usize)> = subgraph
+            .node_v
+            .iter()
+            .map(|visit| (visit.node.as_str(), visit.depth))
+            .collect();
+        assert_eq!(node_v, vec![("b", 0), ("e", 0), ("c", 1)]);
+        assert_eq!(subgraph.edge_v, vec![hop("b", "x", "c")]);
+
+        let other = dm.with_auth(Auth::writer("other", "pen"));
+        assert!(!other
+            .is_reachable("a", "b", &Search::default())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_gc() {
+        let dm = test_db().await;
+        for (paper, path, target_v) in [
+            ("paper", "root->x", vec!["a"]),
+            ("paper", "a->x", vec!["b"]),
+            ("paper", "o1->x", vec!["o2"]),
+            ("paper", "o2->x", vec!["o1", "b"]),
+            ("other", "o3->x", vec!["o4"]),
+            ("other", "a->y", vec!["c"]),
+        ] {
+            let writer = dm.divide(Auth::writer(paper, "pen"));
+            writer
+                .append(
+                    &Path::from_str(path),
+                    target_v.iter().map(|s| s.to_string()).collect(),
+                )
+                .await
+                .unwrap();
+            writer.commit().await.unwrap();
+        }
+        let paper = dm.with_auth(Auth::writer("paper", "pen"));
+        let other = dm.with_auth(Auth::writer("other", "pen"));
+
+        let report = dm
+            .gc(&Gc {
+                dry_run: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(report.orphan_v, vec!["o1", "o2", "o3"]);
+        assert_eq!(report.edge_cnt, 4);
+        assert_eq!(paper.count(&Path::from_str("o2->x")).await.unwrap(), 2);
+
+        // Scoped to its own edges, `a` is an orphan in the other paper.
+        let report = dm
+            .gc(&Gc {
+                paper: Some("other".to_string()),
+                dry_run: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(report.orphan_v, vec!["a", "o3"]);
+
+        let report = dm
+            .gc(&Gc {
+                paper: Some("paper".to_string()),
+                batch_size: 1,
+                pause: Duration::ZERO,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(report.orphan_v, vec!["o1", "o2"]);
+        assert_eq!(report.edge_cnt, 3);
+        assert_eq!(paper.count(&Path::from_str("o2->x")).await.unwrap(), 0);
+        assert_eq!(paper.count(&Path::from_str("root->x->x")).await.unwrap(), 1);
+        assert_eq!(other.count(&Path::from_str("o3->x")).await.unwrap(), 1);
+
+        let report = dm.gc(&Gc::default()).await.unwrap();
+        assert_eq!(report.orphan_v, vec!["o3"]);
+        assert_eq!(other.count(&Path::from_str("o3->x")).await.unwrap(), 0);
+        assert_eq!(other.count(&Path::from_str("a->y")).await.unwrap(), 1);
+        assert_eq!(dm.gc(&Gc::default()).await.unwrap().orphan_v.len(), 0);
+
+        // Linked again between the mark and the sweep, orphans are kept with what they reach.
+        for path in ["o5->x", "o6->x"] {
+            paper
+                .append(&Path::from_str(path), vec!["o6".to_string()])
+                .await
+                .unwrap();
+        }
+        paper.commit().await.unwrap();
+        let mut conn = dm.pool.acquire().await.unwrap();
+        let marked = super::gc::mark(&mut conn, dm.dialect, None).await.unwrap();
+        let orphan_v = super::gc::orphan_v(&mut conn, dm.dialect, None, &marked)
+            .await
+            .unwrap();
+        assert_eq!(orphan_v, vec!["o5", "o6"]);
+        drop(conn);
+        paper
+            .append(&Path::from_str("b->x"), vec!["o5".to_string()])
+            .await
+            .unwrap();
+        paper.commit().await.unwrap();
+        let orphan_set = orphan_v.iter().cloned().collect();
+        let mut tx = dm.pool.begin().await.unwrap();
+        for node_v in orphan_v.chunks(1) {
+            let cnt = super::gc::sweep(&mut tx, dm.dialect, None, node_v, &orphan_set)
+                .await
+                .unwrap();
+            assert_eq!(cnt, 0);
+        }
+        tx.commit().await.unwrap();
+        assert_eq!(paper.count(&Path::from_str("o5->x->x")).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replica() {
+        let replica = test_db().await;
+        replica
+            .append(&Path::from_str("root->name"), vec!["replica".to_string()])
+            .await
+            .unwrap();
+        replica.commit().await.unwrap();
+        let dm = test_db().await.with_read_pool_v(vec![replica.pool.clone()]);
+        let name = Path::from_str("root->name");
+
+        dm.append(&name, vec!["primary".to_string()]).await.unwrap();
+        assert_eq!(dm.get(&name).await.unwrap(), vec!["primary"]);
+        dm.commit().await.unwrap();
+        assert_eq!(dm.get(&name).await.unwrap(), vec!["replica"]);
+        assert_eq!(dm.count(&name).await.unwrap(), 1);
+        assert_eq!(dm.primary().get(&name).await.unwrap(), vec!["primary"]);
+        let paper = dm.with_auth(Auth::printer("root"));
+        assert_eq!(paper.get(&name).await.unwrap(), vec!["replica"]);
+
+        let down = AnyPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("sqlite:///nonexistent/edge.db")
+            .unwrap();
+        let dm = dm.with_read_pool_v(vec![down, replica.pool.clone()]);
+        for _ in 0..3 {
+            assert_eq!(dm.get(&name).await.unwrap(), vec!["replica"]);
+        }
+        let unmigrated = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let dm = dm.with_read_pool_v(vec![unmigrated]);
+        assert!(dm.get(&name).await.is_err());
+        assert!(dm.count(&name).await.is_err());
+
+        let dm = dm.with_read_pool_v(vec![replica.pool.clone()]);
+        replica.pool.close().await;
+        assert_eq!(dm.get(&name).await.unwrap(), vec!["primary"]);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate() {
+        let dm = test_db().await;
+        let edge_v = [
+            ("root->shop", vec!["s1", "s2"]),
+            ("s1->price", vec!["3", "1.5", "x", "3"]),
+            (
+                "s2->price",
+                vec!["-2e1", "", "2024-01-02", "1-2", "1.2.3", "e5"],
+            ),
+        ];
+        for (path, target_v) in &edge_v {
+            dm.append(
+                &Path::from_str(path),
+                target_v.iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        }
+        dm.commit().await.unwrap();
+
+        let rs = dm
+            .aggregate(&Path::from_str("root->shop->price"), None)
+            .await
+            .unwrap();
+        assert_eq!(rs.len(), 1);
+        assert_eq!(rs[0].group, None);
+        assert_eq!(rs[0].count, 10);
+        assert_eq!(rs[0].distinct_count, 9);
+        assert_eq!(rs[0].min, Some(-20.0));
+        assert_eq!(rs[0].max, Some(3.0));
+        assert_eq!(rs[0].sum, Some(-12.5));
+        assert_eq!(rs[0].avg, Some(-3.125));
+
+        let rs = dm
+            .aggregate(&Path::from_str("root->shop->price"), Some(1))
+            .await
+            .unwrap();
+        let row_v: Vec<(Option<&str>, i64, Option<f64>)> = rs
+            .iter()
+            .map(|a| (a.group.as_deref(), a.count, a.sum))
+            .collect();
+        assert_eq!(
+            row_v,
+            vec![(Some("s1"), 4, Some(7.5)), (Some("s2"), 6, Some(-20.0))]
+        );
+
+        let rs = dm
+            .aggregate(&Path::from_str("root->nothing"), None)
+            .await
+            .unwrap();
+        assert_eq!(rs[0].count, 0);
+        assert_eq!(rs[0].sum, None);
+        assert!(dm
+            .aggregate(&Path::from_str("root->shop"), Some(2))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wildcard() {
+        let dm = test_db().await;
+        let edge_v = [
+            ("a->x", vec!["b"]),
+            ("a->y", vec!["c"]),
+            ("d->x", vec!["a"]),
+            ("b->z", vec!["e"]),
+        ];
+        for (path, target_v) in &edge_v {
+            dm.append(
+                &Path::from_str(path),
+                target_v.iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        }
+        dm.commit().await.unwrap();
+
+        assert_eq!(
+            dm.get(&Path::from_str("a->*")).await.unwrap(),
+            vec!["b".to_string(), "c".to_string()]
+        );
+        assert_eq!(
+            dm.get(&Path::from_str("a<-*")).await.unwrap(),
+            vec!["d".to_string()]
+        );
+        assert_eq!(
+            dm.get(&Path::from_str("d->*->*->z")).await.unwrap(),
+            vec!["e".to_string()]
+        );
+        let page = dm
+            .get_pair_page(&Path::from_str("a->*"), &PageQuery::default())
+            .await
+            .unwrap();
+        let pair_v: Vec<(&str, &str)> = page
+            .item_v
+            .iter()
+            .map(|pair| (pair.code.as_str(), pair.target.as_str()))
+            .collect();
+        assert_eq!(pair_v, vec![("x", "b"), ("y", "c")]);
+        assert!(dm
+            .append(&Path::from_str("a->*"), vec!["f".to_string()])
+            .await
+            .is_err());
+
+        let subgraph = dm
+            .neighborhood(
+                "a",
+                &Search {
+                    arrow: "<->".to_string(),
+                    max_depth: 1,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let mut node_v: Vec<&str> = subgraph.node_v.iter().map(|v| v.node.as_str()).collect();
+        node_v.sort();
+        assert_eq!(node_v, vec!["a", "b", "c", "d"]);
+        assert_eq!(subgraph.edge_v.len(), 3);
+        assert!(subgraph.edge_v.contains(&Hop {
+            source: "d".to_string(),
+            code: "x".to_string(),
+            target: "a".to_string(),
+        }));
     }
 }
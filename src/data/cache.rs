@@ -0,0 +1,457 @@
+//! Read-through cache in front of another data manager.
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future, io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use edge_lib::{
+    data::{AsDataManager, Auth},
+    util::Path,
+};
+use serde::Serialize;
+
+use super::dao;
+
+/// Bytes of results kept unless configured otherwise.
+pub const DEFAULT_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Bytes an entry costs beyond its strings.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Edges an auth is allowed to read.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    Root,
+    Paper(String),
+    Pen(String),
+}
+
+impl Scope {
+    fn new(auth: &Auth) -> Self {
+        if auth.is_root() {
+            return Self::Root;
+        }
+        match auth {
+            Auth::Writer(paper, _) => Self::Paper(paper.clone()),
+            Auth::Printer(pen) => Self::Pen(pen.clone()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    scope: Scope,
+    root: String,
+    step_v: Vec<(String, String)>,
+}
+
+impl Key {
+    fn new(auth: &Auth, path: &Path) -> Self {
+        Self {
+            scope: Scope::new(auth),
+            root: path.root.clone(),
+            step_v: path
+                .step_v
+                .iter()
+                .map(|step| (step.arrow.clone(), step.code.clone()))
+                .collect(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        let scope = match &self.scope {
+            Scope::Root => 0,
+            Scope::Paper(s) | Scope::Pen(s) => s.len(),
+        };
+        let step_v: usize = self.step_v.iter().map(|(a, c)| a.len() + c.len()).sum();
+        scope + self.root.len() + step_v
+    }
+}
+
+struct Entry {
+    item_v: Vec<String>,
+    size: usize,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Store {
+    entry_map: HashMap<Key, Entry>,
+    /// Keys by last use, oldest first.
+    lru: BTreeMap<u64, Key>,
+    /// Keys of the entries whose path walks through a code, `child*` counting as `child`.
+    code_map: HashMap<String, HashSet<Key>>,
+    size: usize,
+    tick: u64,
+    /// Bumped by every invalidation so that reads started before it are not stored.
+    generation: u64,
+}
+
+impl Store {
+    fn get(&mut self, key: &Key) -> Option<Vec<String>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entry_map.get_mut(key)?;
+        self.lru.remove(&entry.tick);
+        entry.tick = tick;
+        self.lru.insert(tick, key.clone());
+        Some(entry.item_v.clone())
+    }
+
+    fn insert(&mut self, key: Key, item_v: Vec<String>, capacity: usize) {
+        let size = ENTRY_OVERHEAD
+            + key.size()
+            + item_v
+                .iter()
+                .map(|item| item.len() + ENTRY_OVERHEAD / 2)
+                .sum::<usize>();
+        if size > capacity {
+            return;
+        }
+        self.remove(&key);
+        while self.size + size > capacity {
+            let Some((_, oldest)) = self.lru.pop_first() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+        self.tick += 1;
+        for (_, code) in &key.step_v {
+            self.code_map
+                .entry(dao::base_code(code).to_string())
+                .or_default()
+                .insert(key.clone());
+        }
+        self.lru.insert(self.tick, key.clone());
+        self.size += size;
+        self.entry_map.insert(
+            key,
+            Entry {
+                item_v,
+                size,
+                tick: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &Key) {
+        let Some(entry) = self.entry_map.remove(key) else {
+            return;
+        };
+        self.lru.remove(&entry.tick);
+        self.size -= entry.size;
+        for (_, code) in &key.step_v {
+            let code = dao::base_code(code);
+            if let Some(key_set) = self.code_map.get_mut(code) {
+                key_set.remove(key);
+                if key_set.is_empty() {
+                    self.code_map.remove(code);
+                }
+            }
+        }
+    }
+
+    fn invalidate_code(&mut self, code: &str) {
+        self.generation += 1;
+        for code in [code, dao::WILDCARD] {
+            if let Some(key_set) = self.code_map.remove(code) {
+                for key in &key_set {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+
+    fn invalidate_all(&mut self) {
+        let generation = self.generation + 1;
+        *self = Self {
+            generation,
+            ..Default::default()
+        };
+    }
+}
+
+struct Cache {
+    store: Mutex<Store>,
+    capacity: usize,
+    hit: AtomicU64,
+    miss: AtomicU64,
+}
+
+impl Cache {
+    fn invalidate(&self, written: &Written) {
+        let mut store = self.store.lock().unwrap();
+        if written.is_cleared {
+            store.invalidate_all();
+        } else {
+            for code in &written.code_set {
+                store.invalidate_code(code);
+            }
+        }
+    }
+}
+
+/// Counters of a cache shared by a manager and the managers divided from it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub hit: u64,
+    pub miss: u64,
+    pub entry_count: usize,
+    /// Estimated bytes held.
+    pub size: usize,
+}
+
+/// What a manager wrote since its last commit.
+#[derive(Default)]
+struct Written {
+    is_cleared: bool,
+    code_set: HashSet<String>,
+}
+
+impl Written {
+    fn is_empty(&self) -> bool {
+        !self.is_cleared && self.code_set.is_empty()
+    }
+}
+
+/// Caches the results of `get` per auth scope and path.
+///
+/// Writes evict every entry whose path walks through the written code, and again on commit.
+/// A manager that has written reads past the cache until it commits, so it sees its own
+/// writes and never stores them for others.
+#[derive(Clone)]
+pub struct CacheDataManager {
+    dm: Arc<dyn AsDataManager>,
+    cache: Arc<Cache>,
+    written: Arc<Mutex<Written>>,
+}
+
+impl CacheDataManager {
+    /// Wraps `dm` with a cache holding about `capacity` bytes of results.
+    ///
+    /// `dm` should read from the primary, as a miss read on a lagging replica would be kept.
+    pub fn new(dm: Arc<dyn AsDataManager>, capacity: usize) -> Self {
+        Self {
+            dm,
+            cache: Arc::new(Cache {
+                store: Mutex::new(Store::default()),
+                capacity,
+                hit: AtomicU64::new(0),
+                miss: AtomicU64::new(0),
+            }),
+            written: Arc::new(Mutex::new(Written::default())),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let store = self.cache.store.lock().unwrap();
+        CacheStats {
+            hit: self.cache.hit.load(Ordering::Relaxed),
+            miss: self.cache.miss.load(Ordering::Relaxed),
+            entry_count: store.entry_map.len(),
+            size: store.size,
+        }
+    }
+
+    /// Evicts every entry, for writes made to the database beneath the cache.
+    pub fn invalidate_all(&self) {
+        self.cache.store.lock().unwrap().invalidate_all();
+    }
+
+    /// Records a write and evicts what it may change.
+    fn on_write(&self, code: Option<&str>) {
+        let mut written = self.written.lock().unwrap();
+        match code {
+            Some(code) => {
+                written.code_set.insert(code.to_string());
+                self.cache.store.lock().unwrap().invalidate_code(code);
+            }
+            None => {
+                written.is_cleared = true;
+                self.cache.store.lock().unwrap().invalidate_all();
+            }
+        }
+    }
+}
+
+impl AsDataManager for CacheDataManager {
+    fn get_auth(&self) -> Auth {
+        self.dm.get_auth()
+    }
+
+    fn divide(&self, auth: Auth) -> Arc<dyn AsDataManager> {
+        Arc::new(Self {
+            dm: self.dm.divide(auth),
+            cache: self.cache.clone(),
+            written: Arc::new(Mutex::new(Written::default())),
+        })
+    }
+
+    fn commit(&self) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
+        let this = self.clone();
+        let fut = self.dm.commit();
+        Box::pin(async move {
+            fut.await?;
+            // Entries stored while the transaction was open may predate it.
+            let written = std::mem::take(&mut *this.written.lock().unwrap());
+            this.cache.invalidate(&written);
+            Ok(())
+        })
+    }
+
+    fn append(
+        &self,
+        path: &Path,
+        item_v: Vec<String>,
+    ) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
+        if let Some(step) = path.step_v.last() {
+            self.on_write(Some(&step.code));
+        }
+        self.dm.append(path, item_v)
+    }
+
+    fn set(
+        &self,
+        path: &Path,
+        item_v: Vec<String>,
+    ) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
+        if let Some(step) = path.step_v.last() {
+            self.on_write(Some(&step.code));
+        }
+        self.dm.set(path, item_v)
+    }
+
+    fn get(
+        &self,
+        path: &Path,
+    ) -> Pin<Box<dyn std::future::Future<Output = io::Result<Vec<String>>> + Send>> {
+        if path.step_v.is_empty() || !self.written.lock().unwrap().is_empty() {
+            return self.dm.get(path);
+        }
+        let key = Key::new(&self.dm.get_auth(), path);
+        let generation = {
+            let mut store = self.cache.store.lock().unwrap();
+            if let Some(item_v) = store.get(&key) {
+                self.cache.hit.fetch_add(1, Ordering::Relaxed);
+                return Box::pin(future::ready(Ok(item_v)));
+            }
+            store.generation
+        };
+        self.cache.miss.fetch_add(1, Ordering::Relaxed);
+        let cache = self.cache.clone();
+        let fut = self.dm.get(path);
+        Box::pin(async move {
+            let item_v = fut.await?;
+            let mut store = cache.store.lock().unwrap();
+            if store.generation == generation {
+                store.insert(key, item_v.clone(), cache.capacity);
+            }
+            Ok(item_v)
+        })
+    }
+
+    fn clear(&self) -> Pin<Box<dyn std::future::Future<Output = io::Result<()>> + Send>> {
+        self.on_write(None);
+        self.dm.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{super::test_db, CacheDataManager, DEFAULT_CACHE_SIZE};
+    use edge_lib::{
+        data::{AsDataManager, Auth},
+        util::Path,
+    };
+
+    #[tokio::test]
+    async fn test_cache() {
+        let dm = CacheDataManager::new(Arc::new(test_db().await), DEFAULT_CACHE_SIZE);
+        let path = Path::from_str("root->user");
+        dm.append(&path, vec!["u1".to_string()]).await.unwrap();
+        dm.commit().await.unwrap();
+        assert_eq!(dm.get(&path).await.unwrap(), vec!["u1".to_string()]);
+        assert_eq!(dm.get(&path).await.unwrap(), vec!["u1".to_string()]);
+        let stats = dm.stats();
+        assert_eq!((stats.hit, stats.miss, stats.entry_count), (1, 1, 1));
+
+        // Other scopes do not share entries.
+        let paper = dm.divide(Auth::writer("paper", "pen"));
+        assert!(paper.get(&path).await.unwrap().is_empty());
+        assert_eq!(dm.stats().miss, 2);
+
+        // A writer sees its own writes, others see them after commit.
+        paper.append(&path, vec!["u2".to_string()]).await.unwrap();
+        assert_eq!(paper.get(&path).await.unwrap(), vec!["u2".to_string()]);
+        paper.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&path).await.unwrap(),
+            vec!["u1".to_string(), "u2".to_string()]
+        );
+        assert_eq!(paper.get(&path).await.unwrap(), vec!["u2".to_string()]);
+
+        // Writes to other codes keep the entry.
+        let hit = dm.stats().hit;
+        dm.set(&Path::from_str("u1->name"), vec!["n1".to_string()])
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        dm.get(&path).await.unwrap();
+        assert_eq!(dm.stats().hit, hit + 1);
+
+        paper.clear().await.unwrap();
+        paper.commit().await.unwrap();
+        assert_eq!(dm.stats().entry_count, 0);
+        assert_eq!(dm.get(&path).await.unwrap(), vec!["u1".to_string()]);
+
+        // Recursive steps are evicted by writes to their code.
+        let path = Path::from_str("root->user*");
+        assert_eq!(dm.get(&path).await.unwrap(), vec!["u1".to_string()]);
+        dm.append(&Path::from_str("u1->user"), vec!["u3".to_string()])
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        assert_eq!(
+            dm.get(&path).await.unwrap(),
+            vec!["u1".to_string(), "u3".to_string()]
+        );
+
+        // Wildcard steps are evicted by writes to any code.
+        let path = Path::from_str("u1->*");
+        assert_eq!(dm.get(&path).await.unwrap().len(), 2);
+        dm.append(&Path::from_str("u1->age"), vec!["7".to_string()])
+            .await
+            .unwrap();
+        dm.commit().await.unwrap();
+        assert_eq!(dm.get(&path).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_capacity() {
+        let dm = CacheDataManager::new(Arc::new(test_db().await), 1024);
+        for i in 0..100 {
+            dm.append(&Path::from_str(&format!("n{i}->v")), vec![format!("{i}")])
+                .await
+                .unwrap();
+        }
+        dm.commit().await.unwrap();
+        for i in 0..100 {
+            let path = Path::from_str(&format!("n{i}->v"));
+            assert_eq!(dm.get(&path).await.unwrap(), vec![format!("{i}")]);
+        }
+        let stats = dm.stats();
+        assert!(stats.size <= 1024);
+        assert!(stats.entry_count > 0 && stats.entry_count < 100);
+
+        // The most recent entries survive.
+        dm.get(&Path::from_str("n99->v")).await.unwrap();
+        assert_eq!(dm.stats().hit, 1);
+    }
+}
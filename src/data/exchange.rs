@@ -0,0 +1,468 @@
+//! Text formats the edges of a paper are exported to and imported from.
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    iter::Peekable,
+    str::Chars,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::graph::Hop;
+use crate::util;
+
+/// IRI prefix of nodes in RDF.
+pub const NODE_IRI: &str = "urn:edge:node:";
+/// IRI prefix of codes in RDF.
+pub const CODE_IRI: &str = "urn:edge:code:";
+
+/// Node every paper starts its paths from, kept as it is by `remap`.
+const ROOT: &str = "root";
+
+/// A text format of edges.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// One JSON object per line.
+    #[default]
+    Jsonl,
+    Ntriples,
+    Turtle,
+    /// GraphViz, for looking at rather than importing.
+    Dot,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "application/x-ndjson",
+            Self::Ntriples => "application/n-triples",
+            Self::Turtle => "text/turtle",
+            Self::Dot => "text/vnd.graphviz",
+        }
+    }
+}
+
+/// An edge of a paper, without the paper it belongs to.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Record {
+    pub source: String,
+    pub code: String,
+    pub target: String,
+    pub pen: String,
+}
+
+impl From<Record> for Hop {
+    fn from(record: Record) -> Self {
+        Self {
+            source: record.source,
+            code: record.code,
+            target: record.target,
+        }
+    }
+}
+
+/// Formats `record` as one JSON line, newline included.
+pub fn to_jsonl(record: &Record) -> String {
+    format!("{}\n", serde_json::to_string(record).unwrap())
+}
+
+/// Parses JSON lines of records, skipping blank lines.
+pub fn parse_jsonl(text: &str) -> io::Result<Vec<Record>> {
+    let mut record_v = Vec::new();
+    for (no, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(line)
+            .map_err(|e| io::Error::other(format!("{e}\nwhen parse line {}", no + 1)))?;
+        record_v.push(record);
+    }
+    Ok(record_v)
+}
+
+/// Gives every node of `record_v` a fresh id, returning the id each node got.
+///
+/// A node is a value some record uses as its source; targets naming a node are renamed
+/// with it, other values are kept as they are. `root` stays `root`, and `from`, the paper
+/// the records were exported from, becomes `paper`, so that what hangs off them is still
+/// reached.
+pub fn remap(record_v: &mut [Record], from: Option<&str>, paper: &str) -> BTreeMap<String, String> {
+    let mut id_map = BTreeMap::new();
+    for record in record_v.iter() {
+        id_map
+            .entry(record.source.clone())
+            .or_insert_with(|| match record.source.as_str() {
+                ROOT => ROOT.to_string(),
+                source if Some(source) == from => paper.to_string(),
+                _ => new_id(),
+            });
+    }
+    for record in record_v.iter_mut() {
+        record.source = id_map[&record.source].clone();
+        if let Some(id) = id_map.get(&record.target) {
+            record.target = id.clone();
+        }
+    }
+    id_map
+}
+
+fn new_id() -> String {
+    util::byte_v2hex(&rand::random::<[u8; 16]>())
+}
+
+/// Formats `hop_v` in `format`.
+///
+/// Values some edge leaves from are nodes, other targets are literals.
+pub fn to_text(hop_v: &[Hop], format: Format) -> String {
+    match format {
+        Format::Jsonl => hop_v
+            .iter()
+            .map(|hop| format!("{}\n", serde_json::to_string(hop).unwrap()))
+            .collect(),
+        Format::Ntriples => to_ntriples(hop_v),
+        Format::Turtle => to_turtle(hop_v),
+        Format::Dot => to_dot(hop_v),
+    }
+}
+
+fn to_ntriples(hop_v: &[Hop]) -> String {
+    let node_set: HashSet<&str> = hop_v.iter().map(|hop| hop.source.as_str()).collect();
+    let mut text = String::new();
+    for hop in hop_v {
+        let object = if node_set.contains(hop.target.as_str()) {
+            format!("<{NODE_IRI}{}>", encode(&hop.target))
+        } else {
+            literal(&hop.target)
+        };
+        text.push_str(&format!(
+            "<{NODE_IRI}{}> <{CODE_IRI}{}> {object} .\n",
+            encode(&hop.source),
+            encode(&hop.code)
+        ));
+    }
+    text
+}
+
+fn to_turtle(hop_v: &[Hop]) -> String {
+    let node_set: HashSet<&str> = hop_v.iter().map(|hop| hop.source.as_str()).collect();
+    // Subjects in the order they first appear, each with its predicates.
+    let mut subject_v: Vec<(&str, Vec<&Hop>)> = Vec::new();
+    let mut index_map: HashMap<&str, usize> = HashMap::new();
+    for hop in hop_v {
+        let i = *index_map.entry(&hop.source).or_insert_with(|| {
+            subject_v.push((&hop.source, Vec::new()));
+            subject_v.len() - 1
+        });
+        subject_v[i].1.push(hop);
+    }
+    let mut text = format!("@prefix n: <{NODE_IRI}> .\n@prefix c: <{CODE_IRI}> .\n");
+    for (subject, hop_v) in subject_v {
+        text.push_str(&format!("\nn:{}", encode(subject)));
+        for (i, hop) in hop_v.iter().enumerate() {
+            let object = if node_set.contains(hop.target.as_str()) {
+                format!("n:{}", encode(&hop.target))
+            } else {
+                literal(&hop.target)
+            };
+            let sep = if i + 1 == hop_v.len() { " ." } else { " ;" };
+            let indent = if i == 0 { " " } else { "\n    " };
+            text.push_str(&format!("{indent}c:{} {object}{sep}", encode(&hop.code)));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn to_dot(hop_v: &[Hop]) -> String {
+    let node_set: HashSet<&str> = hop_v.iter().map(|hop| hop.source.as_str()).collect();
+    let mut text = "digraph edge {\n".to_string();
+    let mut literal_set = HashSet::new();
+    for hop in hop_v {
+        if !node_set.contains(hop.target.as_str()) && literal_set.insert(hop.target.as_str()) {
+            text.push_str(&format!("    {} [shape=box];\n", quote(&hop.target)));
+        }
+        text.push_str(&format!(
+            "    {} -> {} [label={}];\n",
+            quote(&hop.source),
+            quote(&hop.target),
+            quote(&hop.code)
+        ));
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// Parses N-Triples into records written by `pen`.
+///
+/// Subjects and objects named by `NODE_IRI` get their node back, other IRIs and blank nodes
+/// are kept whole. Predicates named by `CODE_IRI` get their code back, others are mapped to
+/// the part after their last `#` or `/`.
+pub fn parse_ntriples(text: &str, pen: &str) -> io::Result<Vec<Record>> {
+    let mut record_v = Vec::new();
+    for (no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let record = parse_triple(line, pen)
+            .map_err(|e| io::Error::other(format!("{e}\nwhen parse line {}", no + 1)))?;
+        record_v.push(record);
+    }
+    Ok(record_v)
+}
+
+/// A term of an N-Triples statement.
+enum Term {
+    Iri(String),
+    Blank(String),
+    Literal(String),
+}
+
+fn parse_triple(line: &str, pen: &str) -> io::Result<Record> {
+    let mut chars = line.chars().peekable();
+    let source = match parse_term(&mut chars)? {
+        Term::Iri(iri) => node(&iri),
+        Term::Blank(label) => format!("_:{label}"),
+        Term::Literal(_) => return Err(io::Error::other("a subject can not be a literal")),
+    };
+    let code = match parse_term(&mut chars)? {
+        Term::Iri(iri) => code(&iri),
+        _ => return Err(io::Error::other("a predicate must be an IRI")),
+    };
+    let target = match parse_term(&mut chars)? {
+        Term::Iri(iri) => node(&iri),
+        Term::Blank(label) => format!("_:{label}"),
+        Term::Literal(value) => value,
+    };
+    skip_space(&mut chars);
+    if chars.next() != Some('.') {
+        return Err(io::Error::other("a statement must end with '.'"));
+    }
+    skip_space(&mut chars);
+    if chars.peek().is_some_and(|ch| *ch != '#') {
+        return Err(io::Error::other("unexpected text after '.'"));
+    }
+    Ok(Record {
+        source,
+        code,
+        target,
+        pen: pen.to_string(),
+    })
+}
+
+fn parse_term(chars: &mut Peekable<Chars>) -> io::Result<Term> {
+    skip_space(chars);
+    match chars.next() {
+        Some('<') => {
+            let mut iri = String::new();
+            loop {
+                match chars.next() {
+                    Some('>') => break,
+                    Some('\\') => iri.push(parse_escape(chars)?),
+                    Some(ch) => iri.push(ch),
+                    None => return Err(io::Error::other("unterminated IRI")),
+                }
+            }
+            Ok(Term::Iri(iri))
+        }
+        Some('_') => {
+            if chars.next() != Some(':') {
+                return Err(io::Error::other("a blank node must start with '_:'"));
+            }
+            let mut label = String::new();
+            while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                label.push(ch);
+            }
+            // A label may not end with '.', which then ends the statement.
+            if label.ends_with('.') {
+                label.pop();
+                return Err(io::Error::other("put a space between a blank node and '.'"));
+            }
+            Ok(Term::Blank(label))
+        }
+        Some('"') => {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.push(parse_escape(chars)?),
+                    Some(ch) => value.push(ch),
+                    None => return Err(io::Error::other("unterminated literal")),
+                }
+            }
+            // The datatype or language of a literal is dropped, targets are plain text.
+            if chars.next_if_eq(&'@').is_some() {
+                while chars
+                    .next_if(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+                    .is_some()
+                {}
+            } else if chars.next_if_eq(&'^').is_some() {
+                if chars.next() != Some('^') {
+                    return Err(io::Error::other("a datatype must follow '^^'"));
+                }
+                match parse_term(chars)? {
+                    Term::Iri(_) => {}
+                    _ => return Err(io::Error::other("a datatype must be an IRI")),
+                }
+            }
+            Ok(Term::Literal(value))
+        }
+        Some(ch) => Err(io::Error::other(format!("unexpected '{ch}'"))),
+        None => Err(io::Error::other("missing term")),
+    }
+}
+
+fn parse_escape(chars: &mut Peekable<Chars>) -> io::Result<char> {
+    let len = match chars.next() {
+        Some('t') => return Ok('\t'),
+        Some('b') => return Ok('\u{8}'),
+        Some('n') => return Ok('\n'),
+        Some('r') => return Ok('\r'),
+        Some('f') => return Ok('\u{c}'),
+        Some(ch @ ('"' | '\'' | '\\')) => return Ok(ch),
+        Some('u') => 4,
+        Some('U') => 8,
+        _ => return Err(io::Error::other("invalid escape")),
+    };
+    let hex: String = chars.take(len).collect();
+    u32::from_str_radix(&hex, 16)
+        .ok()
+        .filter(|_| hex.len() == len)
+        .and_then(char::from_u32)
+        .ok_or_else(|| io::Error::other("invalid escape"))
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+}
+
+fn node(iri: &str) -> String {
+    match iri.strip_prefix(NODE_IRI) {
+        Some(name) => decode(name),
+        None => iri.to_string(),
+    }
+}
+
+fn code(iri: &str) -> String {
+    if let Some(name) = iri.strip_prefix(CODE_IRI) {
+        return decode(name);
+    }
+    match iri.rsplit(['#', '/']).next() {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => iri.to_string(),
+    }
+}
+
+/// Percent-encodes every byte but ASCII letters, digits and `_`, which keeps the result
+/// valid both inside an IRI and as a Turtle local name.
+fn encode(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' {
+            text.push(byte as char);
+        } else {
+            text.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    text
+}
+
+fn decode(text: &str) -> String {
+    let byte_v = text.as_bytes();
+    let mut value = Vec::with_capacity(byte_v.len());
+    let mut i = 0;
+    while i < byte_v.len() {
+        let hex = text.get(i + 1..i + 3).filter(|_| byte_v[i] == b'%');
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                value.push(byte);
+                i += 3;
+            }
+            None => {
+                value.push(byte_v[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&value).into_owned()
+}
+
+/// Quotes `value` as an N-Triples or Turtle string literal.
+fn literal(value: &str) -> String {
+    let mut text = String::with_capacity(value.len() + 2);
+    text.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            _ => text.push(ch),
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// Quotes `value` as a DOT id.
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, parse_ntriples, to_text, Format, Hop};
+
+    #[test]
+    fn test_ntriples() {
+        let hop = |source: &str, code: &str, target: &str| Hop {
+            source: source.to_string(),
+            code: code.to_string(),
+            target: target.to_string(),
+        };
+        let hop_v = vec![
+            hop("a", "next", "b b"),
+            hop("b b", "name", "say \"hi\"\n"),
+            hop("a", "name", "a"),
+        ];
+        let text = to_text(&hop_v, Format::Ntriples);
+        assert_eq!(
+            text.lines().next().unwrap(),
+            "<urn:edge:node:a> <urn:edge:code:next> <urn:edge:node:b%20b> ."
+        );
+        let record_v = parse_ntriples(&text, "pen").unwrap();
+        let parsed_v: Vec<Hop> = record_v.into_iter().map(Hop::from).collect();
+        assert_eq!(parsed_v, hop_v);
+
+        let text = "# comment\n\
+            <http://x.org/p#1> <http://xmlns.com/foaf/0.1/knows> _:b1 .\n\
+            _:b1 <http://x.org/v#age> \"42\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n\
+            _:b1 <urn:edge:code:nick%20name> \"\\u00e9t\u{e9}\"@fr .\n";
+        let record_v = parse_ntriples(text, "pen").unwrap();
+        let parsed_v: Vec<Hop> = record_v.into_iter().map(Hop::from).collect();
+        assert_eq!(
+            parsed_v,
+            vec![
+                hop("http://x.org/p#1", "knows", "_:b1"),
+                hop("_:b1", "age", "42"),
+                hop("_:b1", "nick name", "\u{e9}t\u{e9}"),
+            ]
+        );
+        assert!(parse_ntriples("<a> <b> .", "pen").is_err());
+        assert!(parse_ntriples("\"a\" <b> <c> .", "pen").is_err());
+
+        let turtle = to_text(&hop_v, Format::Turtle);
+        assert!(turtle.contains("\nn:a c:next n:b%20b ;\n"));
+        assert!(turtle.contains("\n    c:name n:a .\n"));
+        let dot = to_text(&hop_v, Format::Dot);
+        assert!(dot.contains("    \"a\" -> \"b b\" [label=\"next\"];\n"));
+        assert_eq!(decode(&encode("é/x y")), "é/x y");
+    }
+}
@@ -0,0 +1,216 @@
+//! Mark and sweep of the nodes no root reaches any more.
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    time::Duration,
+};
+
+use serde::Serialize;
+use sqlx::{AnyConnection, Row};
+
+use super::dao::{self, Dialect, Stm};
+
+/// Node every paper starts its paths from.
+const ROOT: &str = "root";
+
+/// Orphans deleted by one transaction unless configured otherwise.
+pub const DEFAULT_GC_BATCH_SIZE: usize = 500;
+
+/// Nodes expanded by one query.
+const FRONTIER_CHUNK: usize = 500;
+
+/// How a collection runs.
+#[derive(Clone, Debug)]
+pub struct Gc {
+    /// Marks and sweeps the edges of this paper only, every edge when `None`.
+    pub paper: Option<String>,
+    /// Reports the orphans without deleting them.
+    pub dry_run: bool,
+    /// Orphans deleted by one transaction.
+    pub batch_size: usize,
+    /// Pause between two batches, leaving the database to other work.
+    pub pause: Duration,
+}
+
+impl Default for Gc {
+    fn default() -> Self {
+        Self {
+            paper: None,
+            dry_run: false,
+            batch_size: DEFAULT_GC_BATCH_SIZE,
+            pause: Duration::from_millis(100),
+        }
+    }
+}
+
+/// What a collection found, and what it deleted unless it was a dry run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GcReport {
+    /// Nodes reached from the roots.
+    pub marked: usize,
+    /// Nodes with live edges that no root reaches.
+    pub orphan_v: Vec<String>,
+    /// Live edges leaving the orphans, deleted or to delete.
+    pub edge_cnt: u64,
+}
+
+/// Returns every node reached from `root` and from the papers along live edges.
+///
+/// Scoped to `paper`, only its edges are walked and it is the only paper started from.
+pub async fn mark(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    paper: Option<&str>,
+) -> io::Result<HashSet<String>> {
+    let mut frontier = vec![ROOT.to_string()];
+    match paper {
+        Some(paper) => frontier.push(paper.to_string()),
+        None => {
+            let stm = Stm::new("select distinct paper from edge_t where deleted_at is null");
+            let rs = stm.fetch_all(conn, dialect).await?;
+            frontier.extend(rs.iter().map(|row| row.get::<String, _>(0)));
+        }
+    }
+    let mut marked: HashSet<String> = frontier.iter().cloned().collect();
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for node_v in frontier.chunks(FRONTIER_CHUNK) {
+            let mut stm = Stm::new("select distinct target from edge_t where deleted_at is null");
+            push_scope_con(&mut stm, paper)
+                .push(" and source in ")
+                .push_in(node_v);
+            for row in stm.fetch_all(conn, dialect).await? {
+                let target: String = row.get(0);
+                if marked.insert(target.clone()) {
+                    next.push(target);
+                }
+            }
+        }
+        frontier = next;
+    }
+    Ok(marked)
+}
+
+/// Returns the sources of live edges that are not `marked`, in order.
+pub async fn orphan_v(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    paper: Option<&str>,
+    marked: &HashSet<String>,
+) -> io::Result<Vec<String>> {
+    let mut stm = Stm::new("select distinct source from edge_t where deleted_at is null");
+    push_scope_con(&mut stm, paper);
+    let mut orphan_v: Vec<String> = stm
+        .fetch_all(conn, dialect)
+        .await?
+        .iter()
+        .map(|row| row.get::<String, _>(0))
+        .filter(|source| !marked.contains(source))
+        .collect();
+    orphan_v.sort();
+    Ok(orphan_v)
+}
+
+/// Counts the live edges leaving the nodes of `node_v`.
+pub async fn count_edge(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    paper: Option<&str>,
+    node_v: &[String],
+) -> io::Result<u64> {
+    let mut cnt = 0;
+    for node_v in node_v.chunks(FRONTIER_CHUNK) {
+        let mut stm = Stm::new("select count(*) from edge_t where deleted_at is null");
+        push_scope_con(&mut stm, paper)
+            .push(" and source in ")
+            .push_in(node_v);
+        let rs = stm.fetch_all(conn, dialect).await?;
+        cnt += rs[0].get::<i64, _>(0) as u64;
+    }
+    Ok(cnt)
+}
+
+/// Deletes the live edges leaving the orphans of `node_v`, returning how many there were.
+///
+/// An orphan that a node out of `orphan_set` reaches again was linked after the mark and is
+/// kept. Run in the transaction of the delete, the check sees every link committed before.
+pub async fn sweep(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    paper: Option<&str>,
+    node_v: &[String],
+    orphan_set: &HashSet<String>,
+) -> io::Result<u64> {
+    let linked = linked(conn, dialect, paper, node_v, orphan_set).await?;
+    let node_v: Vec<String> = node_v
+        .iter()
+        .filter(|node| !linked.contains(*node))
+        .cloned()
+        .collect();
+    let cnt = count_edge(conn, dialect, paper, &node_v).await?;
+    for node_v in node_v.chunks(FRONTIER_CHUNK) {
+        let mut con = Stm::default();
+        push_scope_con(&mut con, paper)
+            .push(" and source in ")
+            .push_in(node_v);
+        dao::delete_edge(conn, dialect, &con).await?;
+    }
+    Ok(cnt)
+}
+
+/// Returns the nodes of `node_v` that a node out of `orphan_set` reaches along live edges.
+///
+/// Walks back from `node_v` through the orphans, then forward from the orphans such a node
+/// points to.
+async fn linked(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    paper: Option<&str>,
+    node_v: &[String],
+    orphan_set: &HashSet<String>,
+) -> io::Result<HashSet<String>> {
+    let mut seen: HashSet<String> = node_v.iter().cloned().collect();
+    let mut frontier = node_v.to_vec();
+    let mut linked_v = Vec::new();
+    let mut next_map: HashMap<String, Vec<String>> = HashMap::new();
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for node_v in frontier.chunks(FRONTIER_CHUNK) {
+            let mut stm =
+                Stm::new("select distinct source, target from edge_t where deleted_at is null");
+            push_scope_con(&mut stm, paper)
+                .push(" and target in ")
+                .push_in(node_v);
+            for row in stm.fetch_all(conn, dialect).await? {
+                let source: String = row.get(0);
+                let target: String = row.get(1);
+                if !orphan_set.contains(&source) {
+                    linked_v.push(target);
+                    continue;
+                }
+                if seen.insert(source.clone()) {
+                    next.push(source.clone());
+                }
+                next_map.entry(source).or_default().push(target);
+            }
+        }
+        frontier = next;
+    }
+    let mut linked = HashSet::new();
+    while let Some(node) = linked_v.pop() {
+        if !linked.insert(node.clone()) {
+            continue;
+        }
+        if let Some(target_v) = next_map.get(&node) {
+            linked_v.extend(target_v.iter().cloned());
+        }
+    }
+    Ok(linked)
+}
+
+fn push_scope_con<'a>(stm: &'a mut Stm, paper: Option<&str>) -> &'a mut Stm {
+    match paper {
+        Some(paper) => stm.push(" and paper = ?").bind(paper),
+        None => stm,
+    }
+}
@@ -0,0 +1,190 @@
+//! Breadth-first searches over the edges an auth can see.
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+use edge_lib::data::Auth;
+use serde::{Deserialize, Serialize};
+use sqlx::AnyConnection;
+
+use super::dao::{self, Dialect};
+
+/// Levels searched unless asked otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 6;
+
+/// Edges followed out of one node unless asked otherwise.
+pub const DEFAULT_MAX_FAN_OUT: usize = 1000;
+
+/// Nodes expanded by one query.
+const FRONTIER_CHUNK: usize = 500;
+
+/// Which edges a search may follow, and how far.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Search {
+    /// Codes that may be walked, every code when empty.
+    pub code_v: Vec<String>,
+    /// `->` walks from source to target, `<-` from target to source, `<->` both ways. Any
+    /// other arrow is refused.
+    pub arrow: String,
+    pub max_depth: usize,
+    /// Most edges followed out of one node, oldest first.
+    pub max_fan_out: usize,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            code_v: Vec::new(),
+            arrow: "->".to_string(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_fan_out: DEFAULT_MAX_FAN_OUT,
+        }
+    }
+}
+
+/// An edge as stored, whichever way it was walked.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Hop {
+    pub source: String,
+    pub code: String,
+    pub target: String,
+}
+
+/// A node found by a search at its distance from the start.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Visit {
+    pub node: String,
+    pub depth: usize,
+}
+
+/// Nodes around a center and the edges between them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Subgraph {
+    pub node_v: Vec<Visit>,
+    pub edge_v: Vec<Hop>,
+}
+
+/// What a search found, in the order it found it.
+#[derive(Default)]
+pub struct Walk {
+    pub visit_v: Vec<Visit>,
+    /// Every edge followed, including those leading back to nodes already found.
+    pub edge_v: Vec<Hop>,
+    /// The node each node was first reached from, with the edge walked.
+    parent: HashMap<String, (String, Hop)>,
+}
+
+impl Walk {
+    /// Edges from a start to `node` along the way it was first reached.
+    pub fn path_to(&self, node: &str) -> Option<Vec<Hop>> {
+        if self
+            .visit_v
+            .iter()
+            .take_while(|visit| visit.depth == 0)
+            .any(|visit| visit.node == node)
+        {
+            return Some(Vec::new());
+        }
+        let mut hop_v = Vec::new();
+        let mut node = node;
+        while let Some((prev, hop)) = self.parent.get(node) {
+            hop_v.push(hop.clone());
+            node = prev;
+        }
+        if hop_v.is_empty() {
+            return None;
+        }
+        hop_v.reverse();
+        Some(hop_v)
+    }
+}
+
+/// Searches breadth first from the nodes of `from_v`, stopping early once `to` is found.
+pub async fn bfs(
+    conn: &mut AnyConnection,
+    dialect: Dialect,
+    auth: &Auth,
+    as_of: Option<i64>,
+    from_v: &[String],
+    to: Option<&str>,
+    search: &Search,
+) -> io::Result<Walk> {
+    if !["->", "<-", "<->"].contains(&search.arrow.as_str()) {
+        return Err(io::Error::other(format!("invalid arrow: {}", search.arrow)));
+    }
+    let mut walk = Walk::default();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut frontier = Vec::with_capacity(from_v.len());
+    for from in from_v {
+        if !visited.insert(from.clone()) {
+            continue;
+        }
+        walk.visit_v.push(Visit {
+            node: from.clone(),
+            depth: 0,
+        });
+        frontier.push(from.clone());
+    }
+    if to.is_some_and(|to| visited.contains(to)) {
+        return Ok(walk);
+    }
+    let arrow_v = if search.arrow == "<->" {
+        vec!["->", "<-"]
+    } else {
+        vec![search.arrow.as_str()]
+    };
+    let mut edge_set: HashSet<Hop> = HashSet::new();
+    for depth in 1..=search.max_depth {
+        let mut next = Vec::new();
+        for node_v in frontier.chunks(FRONTIER_CHUNK) {
+            let mut fan_out: HashMap<String, usize> = HashMap::new();
+            for arrow in &arrow_v {
+                let edge_v =
+                    dao::neighbor(conn, dialect, auth, as_of, node_v, arrow, search).await?;
+                for (node, code, neighbor) in edge_v {
+                    // Both ways share the limit of a node.
+                    let cnt = fan_out.entry(node.clone()).or_default();
+                    *cnt += 1;
+                    if *cnt > search.max_fan_out {
+                        continue;
+                    }
+                    let hop = if *arrow == "->" {
+                        Hop {
+                            source: node.clone(),
+                            code,
+                            target: neighbor.clone(),
+                        }
+                    } else {
+                        Hop {
+                            source: neighbor.clone(),
+                            code,
+                            target: node.clone(),
+                        }
+                    };
+                    if edge_set.insert(hop.clone()) {
+                        walk.edge_v.push(hop.clone());
+                    }
+                    if !visited.insert(neighbor.clone()) {
+                        continue;
+                    }
+                    walk.parent.insert(neighbor.clone(), (node, hop));
+                    walk.visit_v.push(Visit {
+                        node: neighbor.clone(),
+                        depth,
+                    });
+                    if to == Some(neighbor.as_str()) {
+                        return Ok(walk);
+                    }
+                    next.push(neighbor);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    Ok(walk)
+}
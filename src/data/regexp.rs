@@ -0,0 +1,90 @@
+//! The `regexp` function SQLite calls for `x regexp y` but leaves to the application.
+//!
+//! `Any` opens SQLite connections from the url alone, so sqlx's own `with_regexp` can not
+//! be reached; the function is registered on every connection SQLite opens instead.
+use std::{
+    ffi::c_void,
+    os::raw::{c_char, c_int},
+    ptr, slice, str,
+    sync::Once,
+};
+
+use libsqlite3_sys as ffi;
+use regex::Regex;
+
+/// Registers `regexp` on every SQLite connection opened from now on.
+pub fn install() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| unsafe {
+        ffi::sqlite3_auto_extension(Some(register));
+    });
+}
+
+unsafe extern "C" fn register(
+    db: *mut ffi::sqlite3,
+    _: *mut *const c_char,
+    _: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    ffi::sqlite3_create_function_v2(
+        db,
+        c"regexp".as_ptr(),
+        2,
+        ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+        ptr::null_mut(),
+        Some(regexp),
+        None,
+        None,
+        None,
+    )
+}
+
+/// `regexp(pattern, value)`: 1 when `value` matches `pattern`, 0 when not, null when either
+/// is null.
+///
+/// The compiled pattern is kept by SQLite for the rest of the statement.
+unsafe extern "C" fn regexp(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    let arg_v = slice::from_raw_parts(argv, argc as usize);
+    let (Some(pattern), Some(value)) = (text(arg_v[0]), text(arg_v[1])) else {
+        ffi::sqlite3_result_null(ctx);
+        return;
+    };
+    let cached = ffi::sqlite3_get_auxdata(ctx, 0) as *const Regex;
+    let is_match = if cached.is_null() {
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => Box::new(regex),
+            Err(e) => {
+                let msg = format!("{e}\nwhen regexp");
+                ffi::sqlite3_result_error(ctx, msg.as_ptr().cast(), msg.len() as c_int);
+                return;
+            }
+        };
+        let is_match = regex.is_match(value);
+        // SQLite may drop it right away, so it is not used past this point.
+        ffi::sqlite3_set_auxdata(ctx, 0, Box::into_raw(regex).cast(), Some(drop_regex));
+        is_match
+    } else {
+        (*cached).is_match(value)
+    };
+    ffi::sqlite3_result_int(ctx, c_int::from(is_match));
+}
+
+/// Reads `value` as text, `None` when it is null or not UTF-8.
+unsafe fn text<'a>(value: *mut ffi::sqlite3_value) -> Option<&'a str> {
+    if ffi::sqlite3_value_type(value) == ffi::SQLITE_NULL {
+        return None;
+    }
+    let ptr = ffi::sqlite3_value_text(value);
+    if ptr.is_null() {
+        return None;
+    }
+    let len = ffi::sqlite3_value_bytes(value) as usize;
+    str::from_utf8(slice::from_raw_parts(ptr, len)).ok()
+}
+
+unsafe extern "C" fn drop_regex(regex: *mut c_void) {
+    drop(Box::from_raw(regex.cast::<Regex>()));
+}
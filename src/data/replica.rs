@@ -0,0 +1,88 @@
+//! Read replicas that take reads made outside a transaction off the primary.
+use std::{
+    io,
+    sync::atomic::{AtomicI64, AtomicUsize, Ordering},
+};
+
+use sqlx::{pool::PoolConnection, Any, Pool};
+
+use super::dao;
+
+/// Milliseconds a replica is left out after it failed.
+const DOWN_TIME: i64 = 30 * 1000;
+
+/// Whether `e` is the connection to a replica failing, rather than the read itself, which
+/// would fail the same way on the primary.
+pub fn is_lost(e: &io::Error) -> bool {
+    matches!(
+        e.get_ref().and_then(|e| e.downcast_ref::<sqlx::Error>()),
+        Some(
+            sqlx::Error::Io(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed
+        )
+    )
+}
+
+struct Replica {
+    pool: Pool<Any>,
+    /// Left out until this time, in milliseconds since the unix epoch.
+    down_until: AtomicI64,
+}
+
+/// Replicas taken in turn, skipping those that failed lately.
+#[derive(Default)]
+pub struct ReplicaSet {
+    replica_v: Vec<Replica>,
+    next: AtomicUsize,
+}
+
+impl ReplicaSet {
+    pub fn new(pool_v: Vec<Pool<Any>>) -> Self {
+        Self {
+            replica_v: pool_v
+                .into_iter()
+                .map(|pool| Replica {
+                    pool,
+                    down_until: AtomicI64::new(0),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Acquires a connection on the next healthy replica, with the index of that replica,
+    /// `None` when there is none.
+    ///
+    /// A replica failing to give one is left out for a while.
+    pub async fn acquire(&self) -> Option<(usize, PoolConnection<Any>)> {
+        let len = self.replica_v.len();
+        if len == 0 {
+            return None;
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..len {
+            let i = (start + i) % len;
+            let replica = &self.replica_v[i];
+            if replica.down_until.load(Ordering::Relaxed) > dao::now() {
+                continue;
+            }
+            match replica.pool.acquire().await {
+                Ok(conn) => return Some((i, conn)),
+                Err(e) => {
+                    log::warn!("{e}\nwhen acquire replica {i}");
+                    self.set_down(i);
+                }
+            }
+        }
+        None
+    }
+
+    /// Leaves the replica at `i` out for a while.
+    pub fn set_down(&self, i: usize) {
+        self.replica_v[i]
+            .down_until
+            .store(dao::now() + DOWN_TIME, Ordering::Relaxed);
+    }
+}
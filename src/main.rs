@@ -1,7 +1,14 @@
 use std::{io, sync::Arc, time::Duration};
 
 use earth::AsConfig;
-use edge::{connector, data::DbDataManager, server};
+use edge::{
+    connector,
+    data::{
+        self, CacheDataManager, DbDataManager, Gc, DEFAULT_BATCH_SIZE, DEFAULT_CACHE_SIZE,
+        DEFAULT_GC_BATCH_SIZE,
+    },
+    server,
+};
 use edge_lib::{
     data::{Auth, RecDataManager},
     EdgeEngine, ScriptTree,
@@ -15,6 +22,21 @@ struct Config {
     name: String,
     port: u16,
     db_url: String,
+    /// Replicas of `db_url` taking the reads made outside a transaction.
+    read_db_url: Vec<String>,
+    batch_size: usize,
+    /// Bytes of query results to cache, 0 disables the cache.
+    cache_size: usize,
+    /// Days to keep deleted edges for time-travel reads, 0 keeps them forever.
+    retention_days: u16,
+    /// Days deleted papers stay restorable in the trash, 0 purges them at once.
+    trash_days: u16,
+    /// Hours between two collections of orphan nodes, 0 never collects them.
+    gc_hours: u16,
+    /// Orphan nodes deleted by one transaction of a collection.
+    gc_batch_size: usize,
+    /// Milliseconds a collection pauses between two batches.
+    gc_pause_ms: u64,
     thread_num: u8,
     log_level: String,
     key: String,
@@ -28,6 +50,14 @@ impl Default for Config {
             name: "edge".to_string(),
             port: 80,
             db_url: Default::default(),
+            read_db_url: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            cache_size: DEFAULT_CACHE_SIZE,
+            retention_days: 0,
+            trash_days: 30,
+            gc_hours: 0,
+            gc_batch_size: DEFAULT_GC_BATCH_SIZE,
+            gc_pause_ms: 100,
             thread_num: 8,
             log_level: "INFO".to_string(),
             key: format!(""),
@@ -39,6 +69,31 @@ impl Default for Config {
 fn main() -> io::Result<()> {
     let mut arg_v: Vec<String> = std::env::args().collect();
     arg_v.remove(0);
+    let is_migrate = !arg_v.is_empty() && arg_v[0] == "migrate";
+    if is_migrate {
+        arg_v.remove(0);
+    }
+    // `gc [config] [--paper <paper>] [--dry_run]` collects orphan nodes once and exits.
+    let is_gc = !arg_v.is_empty() && arg_v[0] == "gc";
+    let mut gc = Gc::default();
+    if is_gc {
+        arg_v.remove(0);
+        gc.dry_run = take_flag(&mut arg_v, "--dry_run");
+        if let Some(i) = arg_v.iter().position(|arg| arg == "--paper") {
+            if i + 1 >= arg_v.len() {
+                return Err(io::Error::other("--paper needs a value"));
+            }
+            arg_v.remove(i);
+            gc.paper = Some(arg_v.remove(i));
+        }
+    }
+    // `fsck [config] [--repair]` checks the data the server relies on once and exits.
+    let is_fsck = !arg_v.is_empty() && arg_v[0] == "fsck";
+    let mut repair = false;
+    if is_fsck {
+        arg_v.remove(0);
+        repair = take_flag(&mut arg_v, "--repair");
+    }
     let file_name = if !arg_v.is_empty() && !arg_v[0].starts_with("--") {
         arg_v.remove(0)
     } else {
@@ -59,13 +114,63 @@ fn main() -> io::Result<()> {
         .worker_threads(config.thread_num as usize)
         .build()?
         .block_on(async {
-            let pool = sqlx::Pool::connect(&config.db_url)
+            data::install_drivers();
+            let pool = data::pool_options()
+                .connect(&config.db_url)
                 .await
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
-            let dm = Arc::new(RecDataManager::new(Arc::new(DbDataManager::new(
-                pool,
-                Auth::printer(&config.name),
-            ))));
+            let mut read_pool_v = Vec::with_capacity(config.read_db_url.len());
+            for url in &config.read_db_url {
+                // Lazy, so that a replica down at startup is only left out.
+                let read_pool = data::pool_options()
+                    .acquire_timeout(Duration::from_secs(3))
+                    .connect_lazy(url)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                read_pool_v.push(read_pool);
+            }
+            let db_dm = DbDataManager::new(pool, Auth::printer(&config.name))
+                .with_batch_size(config.batch_size)
+                .with_read_pool_v(read_pool_v);
+            let version = db_dm.migrate().await?;
+            log::info!("schema at version {version}");
+            if is_migrate {
+                return Ok(());
+            }
+            gc.batch_size = config.gc_batch_size;
+            gc.pause = Duration::from_millis(config.gc_pause_ms);
+            // Only the server can drop what its cache holds, so one running on this database
+            // with a cache keeps serving the edges deleted here until it restarts.
+            if config.cache_size > 0 && ((is_gc && !gc.dry_run) || (is_fsck && repair)) {
+                log::warn!("restart the server to drop the edges its cache holds");
+            }
+            if is_gc {
+                let report = db_dm.gc(&gc).await?;
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                return Ok(());
+            }
+            if is_fsck {
+                let problem_v = server::fsck::check(&db_dm, repair).await?;
+                println!("{}", serde_json::to_string_pretty(&problem_v).unwrap());
+                if problem_v.iter().any(|problem| !problem.repaired) {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            // Misses fill the cache from the primary, a replica lagging behind could fill it
+            // with what was overwritten since. Scripts then never read from the replicas,
+            // which only take the reads `http_server` makes on `db_dm`.
+            let cache = if config.cache_size > 0 {
+                Some(CacheDataManager::new(
+                    Arc::new(db_dm.primary()),
+                    config.cache_size,
+                ))
+            } else {
+                None
+            };
+            let dm = match &cache {
+                Some(cache) => Arc::new(RecDataManager::new(Arc::new(cache.clone()))),
+                None => Arc::new(RecDataManager::new(Arc::new(db_dm.clone()))),
+            };
             let mut edge_engine = EdgeEngine::new(dm.clone());
             // config.ip, config.port, config.name
             let base_script = [
@@ -93,10 +198,89 @@ fn main() -> io::Result<()> {
             edge_engine.commit().await?;
 
             tokio::spawn(connector::HttpConnector::new(dm.clone()).run());
-            tokio::spawn(server::HttpServer::new(dm.clone()).run());
+            let mut http_server = server::HttpServer::new(dm.clone(), db_dm.clone())
+                .with_trash_days(config.trash_days);
+            if let Some(cache) = &cache {
+                http_server = http_server.with_cache(cache.clone());
+            }
+            tokio::spawn(http_server.run());
+            if config.trash_days > 0 {
+                tokio::spawn(purge_trash(db_dm.clone(), config.trash_days));
+            }
+            if config.gc_hours > 0 {
+                tokio::spawn(collect_orphan(
+                    db_dm.clone(),
+                    cache.clone(),
+                    gc,
+                    config.gc_hours,
+                ));
+            }
+            if config.retention_days > 0 {
+                tokio::spawn(purge_history(db_dm, config.retention_days));
+            }
             loop {
                 log::info!("alive");
+                if let Some(cache) = &cache {
+                    log::debug!("cache: {:?}", cache.stats());
+                }
                 time::sleep(Duration::from_secs(10)).await;
             }
         })
 }
+
+async fn purge_trash(db_dm: DbDataManager, trash_days: u16) {
+    loop {
+        let before = data::now() - trash_days as i64 * 24 * 3600 * 1000;
+        match db_dm.purge_trash(before).await {
+            Ok(cnt) => log::info!("purged {cnt} papers from the trash"),
+            Err(e) => log::warn!("{e}\nwhen purge_trash"),
+        }
+        time::sleep(Duration::from_secs(3600)).await;
+    }
+}
+
+/// Removes `flag` from `arg_v`, telling whether it was there.
+fn take_flag(arg_v: &mut Vec<String>, flag: &str) -> bool {
+    match arg_v.iter().position(|arg| arg == flag) {
+        Some(i) => {
+            arg_v.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+async fn collect_orphan(
+    db_dm: DbDataManager,
+    cache: Option<CacheDataManager>,
+    gc: Gc,
+    gc_hours: u16,
+) {
+    loop {
+        time::sleep(Duration::from_secs(gc_hours as u64 * 3600)).await;
+        match db_dm.gc(&gc).await {
+            Ok(report) => {
+                log::info!(
+                    "collected {} orphan nodes with {} edges",
+                    report.orphan_v.len(),
+                    report.edge_cnt
+                );
+                if let Some(cache) = &cache {
+                    cache.invalidate_all();
+                }
+            }
+            Err(e) => log::warn!("{e}\nwhen gc"),
+        }
+    }
+}
+
+async fn purge_history(db_dm: DbDataManager, retention_days: u16) {
+    loop {
+        let before = data::now() - retention_days as i64 * 24 * 3600 * 1000;
+        match db_dm.purge_history(before).await {
+            Ok(cnt) => log::info!("purged {cnt} deleted edges"),
+            Err(e) => log::warn!("{e}\nwhen purge_history"),
+        }
+        time::sleep(Duration::from_secs(3600)).await;
+    }
+}
@@ -4,17 +4,77 @@ mod service;
 
 use std::{io, sync::Arc};
 
-use axum::{routing, Router};
+use axum::{extract::FromRef, routing, Router};
 use edge_lib::{data::AsDataManager, EdgeEngine, ScriptTree};
 use serde::Deserialize;
 
+use crate::data::{CacheDataManager, DbDataManager, FindQuery, Format, PageQuery, Search};
+
+pub use service::fsck;
+
+/// Most targets one page may carry.
+const MAX_PAGE_LIMIT: usize = 10000;
+
+/// Most levels a graph search may walk.
+const MAX_SEARCH_DEPTH: usize = 32;
+
+/// Most edges a graph search may follow out of one node.
+const MAX_SEARCH_FAN_OUT: usize = 10000;
+
+/// State shared by the handlers.
+///
+/// Handlers that only need the generic data manager extract `State<Arc<dyn AsDataManager>>`.
+#[derive(Clone)]
+struct AppState {
+    dm: Arc<dyn AsDataManager>,
+    /// The database behind `dm`, for reads the generic interface does not offer.
+    db: DbDataManager,
+    /// The cache between `dm` and `db`, to evict after writing to `db` directly.
+    cache: Option<CacheDataManager>,
+    /// Days deleted papers stay in the trash, 0 purges them at once.
+    trash_days: u16,
+}
+
+impl FromRef<AppState> for Arc<dyn AsDataManager> {
+    fn from_ref(state: &AppState) -> Self {
+        state.dm.clone()
+    }
+}
+
+impl FromRef<AppState> for DbDataManager {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
 pub struct HttpServer {
     dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
+    cache: Option<CacheDataManager>,
+    trash_days: u16,
 }
 
 impl HttpServer {
-    pub fn new(dm: Arc<dyn AsDataManager>) -> Self {
-        Self { dm }
+    pub fn new(dm: Arc<dyn AsDataManager>, db: DbDataManager) -> Self {
+        Self {
+            dm,
+            db,
+            cache: None,
+            trash_days: 0,
+        }
+    }
+
+    /// Tells the server which cache sits between `dm` and `db`.
+    pub fn with_cache(self, cache: CacheDataManager) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Keeps deleted papers restorable for `trash_days` days.
+    pub fn with_trash_days(self, trash_days: u16) -> Self {
+        Self { trash_days, ..self }
     }
 
     pub async fn run(self) -> io::Result<()> {
@@ -62,12 +122,72 @@ impl HttpServer {
                 routing::delete(main::delete_paper),
             )
             .route(&format!("/{}/paper", name), routing::get(main::get_paper))
+            .route(
+                &format!("/{}/paper/export", name),
+                routing::get(main::get_paper_export),
+            )
+            .route(
+                &format!("/{}/paper/import", name),
+                routing::post(main::post_paper_import),
+            )
+            .route(
+                &format!("/{}/paper/restore", name),
+                routing::post(main::post_paper_restore),
+            )
+            .route(
+                &format!("/{}/paper/trash", name),
+                routing::get(main::get_paper_trash),
+            )
             .route(&format!("/{}/paper", name), routing::post(main::post_paper))
             .route(
                 &format!("/{}/paper/writer", name),
                 routing::post(main::get_paper_writer),
             )
-            .with_state(self.dm);
+            .route(&format!("/{}/path", name), routing::get(main::get_path))
+            .route(&format!("/{}/path", name), routing::post(main::post_path))
+            .route(
+                &format!("/{}/path/pair", name),
+                routing::get(main::get_path_pair),
+            )
+            .route(
+                &format!("/{}/path/pair", name),
+                routing::post(main::post_path_pair),
+            )
+            .route(&format!("/{}/find", name), routing::get(main::get_find))
+            .route(
+                &format!("/{}/path/aggregate", name),
+                routing::get(main::get_path_aggregate),
+            )
+            .route(
+                &format!("/{}/path/export", name),
+                routing::post(main::post_path_export),
+            )
+            .route(
+                &format!("/{}/path/stream", name),
+                routing::get(main::get_path_stream),
+            )
+            .route(
+                &format!("/{}/graph/bfs", name),
+                routing::post(main::post_graph_bfs),
+            )
+            .route(
+                &format!("/{}/graph/shortest_path", name),
+                routing::post(main::post_graph_shortest_path),
+            )
+            .route(
+                &format!("/{}/graph/neighborhood", name),
+                routing::post(main::post_graph_neighborhood),
+            )
+            .route(
+                &format!("/{}/graph/reachable", name),
+                routing::post(main::post_graph_reachable),
+            )
+            .with_state(AppState {
+                dm: self.dm,
+                db: self.db,
+                cache: self.cache,
+                trash_days: self.trash_days,
+            });
         // run our app with hyper, listening globally on port 3000
         let address = format!("{}:{}", ip, port);
         log::info!("serving at {address}/{}", name);
@@ -84,26 +204,89 @@ struct Paper {
     manager_v: Vec<String>,
 }
 
+#[derive(Deserialize)]
+struct GraphQuery {
+    #[serde(default)]
+    paper: String,
+    from: String,
+    #[serde(default)]
+    to: String,
+    #[serde(flatten)]
+    search: Search,
+}
+
+#[derive(Deserialize)]
+struct PathExportQuery {
+    #[serde(default)]
+    paper: String,
+    path: String,
+    #[serde(default)]
+    format: Format,
+    #[serde(flatten)]
+    search: Search,
+}
+
+#[derive(Deserialize)]
+struct PathQuery {
+    #[serde(default)]
+    paper: String,
+    path: String,
+}
+
+/// A page read whose filters do not fit in a query string.
+#[derive(Deserialize)]
+struct PageBody {
+    #[serde(default)]
+    paper: String,
+    path: String,
+    #[serde(flatten)]
+    page: PageQuery,
+}
+
+#[derive(Deserialize)]
+struct FindParam {
+    #[serde(default)]
+    paper: String,
+    #[serde(flatten)]
+    find: FindQuery,
+}
+
+#[derive(Deserialize)]
+struct AggregateQuery {
+    /// Number of steps from the root to the nodes to group by.
+    #[serde(default)]
+    group_by: Option<usize>,
+}
+
 mod main {
     use std::{collections::HashMap, sync::Arc};
 
     use axum::{
+        body::Body,
         extract::{Query, State},
-        http::{HeaderMap, Response, StatusCode},
+        http::{header, HeaderMap, Response, StatusCode},
         Json,
     };
     use edge_lib::{data::AsDataManager, util::Path, ScriptTree};
+    use futures_util::stream;
     use serde::Deserialize;
 
-    use crate::err;
+    use crate::{
+        data::{self, DbDataManager, Format, PageQuery, Search},
+        err,
+    };
 
-    use super::{crypto, service, Paper};
+    use super::{
+        crypto, service, AggregateQuery, AppState, FindParam, GraphQuery, PageBody, Paper,
+        PathExportQuery, PathQuery, MAX_PAGE_LIMIT, MAX_SEARCH_DEPTH, MAX_SEARCH_FAN_OUT,
+    };
 
     pub async fn post_register(
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
         Json(auth): Json<crypto::Auth>,
     ) -> (StatusCode, String) {
-        match service::register(dm, &auth).await {
+        match service::register(dm, db, &auth).await {
             Ok(_) => (StatusCode::OK, format!("success")),
             Err(e) => {
                 log::warn!("when http_register:\n{e}");
@@ -155,6 +338,7 @@ mod main {
     pub async fn post_execute(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
         body: String,
     ) -> Response<String> {
         let (writer, printer) = match parse_auth_by_header(dm.clone(), &hm).await {
@@ -167,7 +351,7 @@ mod main {
         let body_json = json::parse(&body).unwrap();
         let paper = body_json["paper"].as_str().unwrap();
         let script_vn = &body_json["script"];
-        match service::execute(dm, writer, paper.to_string(), printer, script_vn).await {
+        match service::execute(dm, db, writer, paper.to_string(), printer, script_vn).await {
             Ok(s) => Response::builder().status(StatusCode::OK).body(s).unwrap(),
             Err(e) => {
                 log::warn!("when post_execute:\n{e}");
@@ -185,6 +369,7 @@ mod main {
     pub async fn post_execute1(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
         body: String,
     ) -> Response<String> {
         let (writer, printer) = match parse_auth_by_header(dm.clone(), &hm).await {
@@ -195,7 +380,7 @@ mod main {
             }
         };
         let swp: ScriptWithPaper = serde_json::from_str(&body).unwrap();
-        match service::execute1(dm, writer, swp.paper, printer, &swp.script).await {
+        match service::execute1(dm, db, writer, swp.paper, printer, &swp.script).await {
             Ok(s) => Response::builder().status(StatusCode::OK).body(s).unwrap(),
             Err(e) => {
                 log::warn!("when post_execute1:\n{e}");
@@ -228,21 +413,43 @@ mod main {
     #[derive(Deserialize)]
     pub struct PaperQuery {
         paper_id: String,
+        /// Removes the paper for good instead of moving it into the trash.
+        #[serde(default)]
+        purge: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ExchangeQuery {
+        paper_id: String,
+        #[serde(default)]
+        format: Format,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ImportQuery {
+        paper_id: String,
+        #[serde(default)]
+        format: Format,
+        /// Paper the edges were exported from, whose node becomes `paper_id`.
+        #[serde(default)]
+        from_paper_id: Option<String>,
     }
 
     pub async fn delete_paper(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(state): State<AppState>,
         Query(paper): Query<PaperQuery>,
     ) -> Response<String> {
-        let (writer, _) = match parse_auth_by_header(dm.clone(), &hm).await {
+        let (writer, _) = match parse_auth_by_header(dm, &hm).await {
             Ok(rs) => rs,
             Err(e) => {
                 log::warn!("{e}\nwhen delete_paper");
                 return map_err(e);
             }
         };
-        match service::delete_paper(dm, writer, paper.paper_id).await {
+        let purge = paper.purge || state.trash_days == 0;
+        match service::delete_paper(state.db, state.cache, writer, paper.paper_id, purge).await {
             Ok(_) => Response::builder()
                 .status(StatusCode::OK)
                 .body("success".to_string())
@@ -254,6 +461,141 @@ mod main {
         }
     }
 
+    /// Brings a paper of the caller back from the trash.
+    pub async fn post_paper_restore(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(state): State<AppState>,
+        Query(paper): Query<PaperQuery>,
+    ) -> Response<String> {
+        let (writer, _) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_paper_restore");
+                return map_err(e);
+            }
+        };
+        match service::restore_paper(state.db, state.cache, writer, paper.paper_id).await {
+            Ok(_) => Response::builder()
+                .status(StatusCode::OK)
+                .body("success".to_string())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_paper_restore");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Streams the edges of a paper as JSON lines, or sends them whole in another format.
+    pub async fn get_paper_export(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(query): Query<ExchangeQuery>,
+    ) -> Response<Body> {
+        let (writer, _) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_paper_export");
+                return map_err(e).map(Body::from);
+            }
+        };
+        if query.format != Format::Jsonl {
+            return match service::export_paper_text(db, writer, query.paper_id, query.format).await
+            {
+                Ok(text) => Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, query.format.content_type())
+                    .body(Body::from(text))
+                    .unwrap(),
+                Err(e) => {
+                    log::warn!("{e}\nwhen get_paper_export");
+                    map_err(e).map(Body::from)
+                }
+            };
+        }
+        let receiver = match service::export_paper(db, writer, query.paper_id).await {
+            Ok(r) => r,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_paper_export");
+                return map_err(e).map(Body::from);
+            }
+        };
+        let line_stream = stream::unfold(receiver, |mut receiver| async move {
+            let line = receiver.recv().await?.map(|record| data::to_jsonl(&record));
+            Some((line, receiver))
+        });
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, Format::Jsonl.content_type())
+            .body(Body::from_stream(line_stream))
+            .unwrap()
+    }
+
+    /// Imports JSON lines or N-Triples of edges into a paper under fresh node ids.
+    pub async fn post_paper_import(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(state): State<AppState>,
+        Query(query): Query<ImportQuery>,
+        body: String,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_paper_import");
+                return map_err(e);
+            }
+        };
+        match service::import_paper(
+            state.db,
+            state.cache,
+            writer,
+            printer,
+            query.paper_id,
+            query.from_paper_id,
+            query.format,
+            &body,
+        )
+        .await
+        {
+            Ok(id_map) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&id_map).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_paper_import");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Lists the papers of the caller in the trash.
+    pub async fn get_paper_trash(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+    ) -> Response<String> {
+        let (writer, _) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_paper_trash");
+                return map_err(e);
+            }
+        };
+        match service::list_trash(db, writer).await {
+            Ok(trashed_v) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&trashed_v).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen get_paper_trash");
+                map_err(e)
+            }
+        }
+    }
+
     pub async fn get_paper(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
@@ -280,6 +622,7 @@ mod main {
     pub async fn get_paper_writer(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
         Query(paper): Query<PaperQuery>,
     ) -> Response<String> {
         let (writer, _) = match parse_auth_by_header(dm.clone(), &hm).await {
@@ -289,7 +632,7 @@ mod main {
                 return map_err(e);
             }
         };
-        match service::get_paper_writer(dm, writer, paper.paper_id).await {
+        match service::get_paper_writer(dm, db, writer, paper.paper_id).await {
             Ok(s) => Response::builder()
                 .status(StatusCode::OK)
                 .body(s)
@@ -304,6 +647,7 @@ mod main {
     pub async fn post_paper(
         hm: HeaderMap,
         State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
         Json(paper): Json<Paper>,
     ) -> Response<String> {
         let (writer, _) = match parse_auth_by_header(dm.clone(), &hm).await {
@@ -313,7 +657,7 @@ mod main {
                 return map_err(e);
             }
         };
-        match service::update_paper(dm, writer, paper).await {
+        match service::update_paper(dm, db, writer, paper).await {
             Ok(_) => Response::builder()
                 .status(StatusCode::OK)
                 .body("success".to_string())
@@ -325,6 +669,353 @@ mod main {
         }
     }
 
+    pub async fn get_path(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(path): Query<PathQuery>,
+        Query(mut page): Query<PageQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm.clone(), &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path");
+                return map_err(e);
+            }
+        };
+        page.limit = page.limit.min(MAX_PAGE_LIMIT);
+        match service::get_page(db, writer, path.paper, printer, &path.path, &page).await {
+            Ok(page) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&page).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Reads a page of the targets of a path, sorted and filtered as the body asks.
+    pub async fn post_path(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(mut body): Json<PageBody>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path");
+                return map_err(e);
+            }
+        };
+        body.page.limit = body.page.limit.min(MAX_PAGE_LIMIT);
+        match service::get_page(db, writer, body.paper, printer, &body.path, &body.page).await {
+            Ok(page) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&page).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Reads a page of `(code, target)` pairs, e.g. of `node->*`.
+    pub async fn get_path_pair(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(path): Query<PathQuery>,
+        Query(mut page): Query<PageQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_pair");
+                return map_err(e);
+            }
+        };
+        page.limit = page.limit.min(MAX_PAGE_LIMIT);
+        match service::get_pair_page(db, writer, path.paper, printer, &path.path, &page).await {
+            Ok(page) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&page).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_pair");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Reads a page of `(code, target)` pairs, sorted and filtered as the body asks.
+    pub async fn post_path_pair(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(mut body): Json<PageBody>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path_pair");
+                return map_err(e);
+            }
+        };
+        body.page.limit = body.page.limit.min(MAX_PAGE_LIMIT);
+        match service::get_pair_page(db, writer, body.paper, printer, &body.path, &body.page).await
+        {
+            Ok(page) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&page).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path_pair");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Finds a page of the edges of a code whose targets match a text.
+    pub async fn get_find(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(param): Query<FindParam>,
+        Query(mut page): Query<PageQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_find");
+                return map_err(e);
+            }
+        };
+        page.limit = page.limit.min(MAX_PAGE_LIMIT);
+        match service::find(db, writer, param.paper, printer, &param.find, &page).await {
+            Ok(page) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&page).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen get_find");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Summarizes the targets of a path, optionally grouped by one of its steps.
+    pub async fn get_path_aggregate(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(path): Query<PathQuery>,
+        Query(aggregate): Query<AggregateQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_aggregate");
+                return map_err(e);
+            }
+        };
+        match service::aggregate(
+            db,
+            writer,
+            path.paper,
+            printer,
+            &path.path,
+            aggregate.group_by,
+        )
+        .await
+        {
+            Ok(rs) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&rs).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_aggregate");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Streams the targets of a path as JSON lines.
+    pub async fn get_path_stream(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Query(path): Query<PathQuery>,
+    ) -> Response<Body> {
+        let (writer, printer) = match parse_auth_by_header(dm.clone(), &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_stream");
+                return map_err(e).map(Body::from);
+            }
+        };
+        let receiver = match service::stream(db, writer, path.paper, printer, &path.path).await {
+            Ok(r) => r,
+            Err(e) => {
+                log::warn!("{e}\nwhen get_path_stream");
+                return map_err(e).map(Body::from);
+            }
+        };
+        let line_stream = stream::unfold(receiver, |mut receiver| async move {
+            let line = receiver
+                .recv()
+                .await?
+                .map(|item| format!("{}\n", serde_json::to_string(&item).unwrap()));
+            Some((line, receiver))
+        });
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from_stream(line_stream))
+            .unwrap()
+    }
+
+    pub async fn post_graph_bfs(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(query): Json<GraphQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_bfs");
+                return map_err(e);
+            }
+        };
+        match service::bfs(db, writer, printer, &limit_search(query)).await {
+            Ok(visit_v) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&visit_v).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_bfs");
+                map_err(e)
+            }
+        }
+    }
+
+    pub async fn post_graph_shortest_path(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(query): Json<GraphQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_shortest_path");
+                return map_err(e);
+            }
+        };
+        match service::shortest_path(db, writer, printer, &limit_search(query)).await {
+            Ok(hop_v) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&hop_v).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_shortest_path");
+                map_err(e)
+            }
+        }
+    }
+
+    pub async fn post_graph_neighborhood(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(query): Json<GraphQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_neighborhood");
+                return map_err(e);
+            }
+        };
+        match service::neighborhood(db, writer, printer, &limit_search(query)).await {
+            Ok(subgraph) => Response::builder()
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&subgraph).unwrap())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_neighborhood");
+                map_err(e)
+            }
+        }
+    }
+
+    pub async fn post_graph_reachable(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(query): Json<GraphQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_reachable");
+                return map_err(e);
+            }
+        };
+        match service::is_reachable(db, writer, printer, &limit_search(query)).await {
+            Ok(is_reachable) => Response::builder()
+                .status(StatusCode::OK)
+                .body(is_reachable.to_string())
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_graph_reachable");
+                map_err(e)
+            }
+        }
+    }
+
+    /// Exports the subgraph found from the targets of a path.
+    pub async fn post_path_export(
+        hm: HeaderMap,
+        State(dm): State<Arc<dyn AsDataManager>>,
+        State(db): State<DbDataManager>,
+        Json(mut query): Json<PathExportQuery>,
+    ) -> Response<String> {
+        let (writer, printer) = match parse_auth_by_header(dm, &hm).await {
+            Ok(rs) => rs,
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path_export");
+                return map_err(e);
+            }
+        };
+        limit(&mut query.search);
+        match service::path_subgraph(db, writer, printer, &query).await {
+            Ok(subgraph) => Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, query.format.content_type())
+                .body(data::to_text(&subgraph.edge_v, query.format))
+                .unwrap(),
+            Err(e) => {
+                log::warn!("{e}\nwhen post_path_export");
+                map_err(e)
+            }
+        }
+    }
+
+    fn limit_search(mut query: GraphQuery) -> GraphQuery {
+        limit(&mut query.search);
+        query
+    }
+
+    fn limit(search: &mut Search) {
+        search.max_depth = search.max_depth.min(MAX_SEARCH_DEPTH);
+        search.max_fan_out = search.max_fan_out.min(MAX_SEARCH_FAN_OUT);
+    }
+
     async fn parse_auth_by_header(
         dm: Arc<dyn AsDataManager>,
         hm: &HeaderMap,
@@ -0,0 +1,321 @@
+//! Checks of the invariants the service relies on, with repairs for those that have a safe
+//! one.
+use std::{collections::BTreeMap, io};
+
+use edge_lib::{data::AsDataManager, util::Path};
+use serde::Serialize;
+
+use crate::data::{DbDataManager, FindMode, FindQuery, PageQuery};
+
+/// Values `root` holds once each.
+const ROOT_CODE_V: [&str; 5] = ["name", "ip", "port", "path", "key"];
+
+/// Edges read by one page.
+const PAGE_LIMIT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A user of `root->user` can not log in, repaired by leaving `root->user`.
+    UserWithoutEmail,
+    UserWithoutPassword,
+    UserWithManyEmails,
+    /// Users sharing an email, reported at the email.
+    DuplicateEmail,
+    /// Papers listed under an email no user has, reported at the email.
+    UnknownOwner,
+    PaperWithManyOwners,
+    PaperWithoutName,
+    /// Writers of a paper no user has as email, repaired by removing them.
+    UnknownWriter,
+    /// Managers of a paper no user has as email, repaired by removing them.
+    UnknownManager,
+    /// More than one value of `root->code`, repaired by keeping the newest.
+    DuplicateRootValue,
+}
+
+/// A broken invariant.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Problem {
+    pub kind: Kind,
+    /// Node the invariant is broken at.
+    pub node: String,
+    /// Code of the edges at fault.
+    pub code: String,
+    /// Targets at fault, or the nodes sharing `node` for duplicates.
+    pub value_v: Vec<String>,
+    pub repaired: bool,
+}
+
+/// Reports every broken invariant of what `db` sees on the primary, repairing those that
+/// can be when `repair` is set.
+///
+/// Repairs are committed together once everything is checked.
+pub async fn check(db: &DbDataManager, repair: bool) -> io::Result<Vec<Problem>> {
+    let db = db.primary().with_auth(db.get_auth());
+    let mut problem_v = Vec::new();
+
+    // Users
+    let user_v = db.get(&Path::from_str("root->user")).await?;
+    let mut user_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut lost_user_v = Vec::new();
+    for user in &user_v {
+        let email_v = db.get(&Path::from_str(&format!("{user}->email"))).await?;
+        match email_v.len() {
+            0 => {
+                lost_user_v.push(user.clone());
+                problem_v.push(Problem {
+                    repaired: repair,
+                    ..problem(Kind::UserWithoutEmail, user, "email", vec![])
+                });
+            }
+            1 => {}
+            _ => problem_v.push(problem(
+                Kind::UserWithManyEmails,
+                user,
+                "email",
+                email_v.clone(),
+            )),
+        }
+        for email in email_v {
+            user_map.entry(email).or_default().push(user.clone());
+        }
+        if db
+            .count(&Path::from_str(&format!("{user}->password")))
+            .await?
+            == 0
+        {
+            problem_v.push(problem(Kind::UserWithoutPassword, user, "password", vec![]));
+        }
+    }
+    for (email, user_v) in &user_map {
+        if user_v.len() > 1 {
+            problem_v.push(problem(
+                Kind::DuplicateEmail,
+                email,
+                "email",
+                user_v.clone(),
+            ));
+        }
+    }
+    if repair {
+        db.delete_target("root", "user", &lost_user_v).await?;
+    }
+
+    // Papers
+    let mut owner_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut paper_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let find = FindQuery {
+        code: "paper".to_string(),
+        text: String::new(),
+        mode: FindMode::Prefix,
+    };
+    let mut query = PageQuery {
+        limit: PAGE_LIMIT,
+        ..Default::default()
+    };
+    loop {
+        let page = db.find(&find, &query).await?;
+        for hop in page.item_v {
+            owner_map
+                .entry(hop.source.clone())
+                .or_default()
+                .push(hop.target.clone());
+            paper_map.entry(hop.target).or_default().push(hop.source);
+        }
+        match page.cursor {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    for (owner, paper_v) in owner_map {
+        if !user_map.contains_key(&owner) {
+            problem_v.push(problem(Kind::UnknownOwner, &owner, "paper", paper_v));
+        }
+    }
+    for (paper, owner_v) in paper_map {
+        if owner_v.len() > 1 {
+            problem_v.push(problem(Kind::PaperWithManyOwners, &paper, "paper", owner_v));
+        }
+        if db.count(&Path::from_str(&format!("{paper}->name"))).await? == 0 {
+            problem_v.push(problem(Kind::PaperWithoutName, &paper, "name", vec![]));
+        }
+        for (kind, code) in [
+            (Kind::UnknownWriter, "writer"),
+            (Kind::UnknownManager, "manager"),
+        ] {
+            let path = Path::from_str(&format!("{paper}->{code}"));
+            let unknown_v: Vec<String> = db
+                .get(&path)
+                .await?
+                .into_iter()
+                .filter(|email| !user_map.contains_key(email))
+                .collect();
+            if unknown_v.is_empty() {
+                continue;
+            }
+            if repair {
+                db.delete_target(&paper, code, &unknown_v).await?;
+            }
+            problem_v.push(Problem {
+                repaired: repair,
+                ..problem(kind, &paper, code, unknown_v)
+            });
+        }
+    }
+
+    // Root
+    for code in ROOT_CODE_V {
+        let path = Path::from_str(&format!("root->{code}"));
+        let value_v = db.get(&path).await?;
+        if value_v.len() < 2 {
+            continue;
+        }
+        if repair {
+            db.set(&path, vec![value_v.last().unwrap().clone()]).await?;
+        }
+        problem_v.push(Problem {
+            repaired: repair,
+            ..problem(Kind::DuplicateRootValue, "root", code, value_v)
+        });
+    }
+
+    if repair {
+        db.commit().await?;
+    }
+    Ok(problem_v)
+}
+
+fn problem(kind: Kind, node: &str, code: &str, value_v: Vec<String>) -> Problem {
+    Problem {
+        kind,
+        node: node.to_string(),
+        code: code.to_string(),
+        value_v,
+        repaired: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{test_db, OP_DELETE, OP_INSERT};
+    use edge_lib::{
+        data::{AsDataManager, Auth},
+        util::Path,
+    };
+
+    use super::{check, Kind};
+
+    #[tokio::test]
+    async fn test_check() {
+        let db = test_db().await.with_auth(Auth::printer("edge"));
+        for (path, value_v) in [
+            ("root->user", vec!["u1", "u2", "u3"]),
+            ("u1->email", vec!["a"]),
+            ("u1->password", vec!["pw"]),
+            ("u2->email", vec!["a"]),
+            ("u2->password", vec!["pw"]),
+            ("a->paper", vec!["p1"]),
+            ("ghost->paper", vec!["p2"]),
+            ("p1->name", vec!["n"]),
+            ("p1->writer", vec!["a", "nobody"]),
+            ("p1->manager", vec!["a"]),
+            ("root->name", vec!["old", "new"]),
+        ] {
+            db.append(
+                &Path::from_str(path),
+                value_v.iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        }
+        db.commit().await.unwrap();
+
+        let problem_v = check(&db, false).await.unwrap();
+        let found_v: Vec<(Kind, &str)> = problem_v
+            .iter()
+            .map(|problem| (problem.kind, problem.node.as_str()))
+            .collect();
+        assert_eq!(
+            found_v,
+            vec![
+                (Kind::UserWithoutEmail, "u3"),
+                (Kind::UserWithoutPassword, "u3"),
+                (Kind::DuplicateEmail, "a"),
+                (Kind::UnknownOwner, "ghost"),
+                (Kind::UnknownWriter, "p1"),
+                (Kind::PaperWithoutName, "p2"),
+                (Kind::DuplicateRootValue, "root"),
+            ]
+        );
+        assert!(problem_v.iter().all(|problem| !problem.repaired));
+        assert_eq!(problem_v[4].value_v, vec!["nobody"]);
+
+        let seq = db
+            .changes_since(0, usize::MAX)
+            .await
+            .unwrap()
+            .last()
+            .unwrap()
+            .seq;
+        let problem_v = check(&db, true).await.unwrap();
+        // Repairs delete only the edges at fault.
+        let change_v: Vec<(String, String, String)> = db
+            .changes_since(seq, usize::MAX)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.op, change.code, change.target))
+            .collect();
+        let change_v: Vec<(&str, &str, &str)> = change_v
+            .iter()
+            .map(|(op, code, target)| (op.as_str(), code.as_str(), target.as_str()))
+            .collect();
+        assert_eq!(
+            change_v,
+            vec![
+                (OP_DELETE, "user", "u3"),
+                (OP_DELETE, "writer", "nobody"),
+                (OP_DELETE, "name", "old"),
+                (OP_DELETE, "name", "new"),
+                (OP_INSERT, "name", "new"),
+            ]
+        );
+        let repaired_v: Vec<Kind> = problem_v
+            .iter()
+            .filter(|problem| problem.repaired)
+            .map(|problem| problem.kind)
+            .collect();
+        assert_eq!(
+            repaired_v,
+            vec![
+                Kind::UserWithoutEmail,
+                Kind::UnknownWriter,
+                Kind::DuplicateRootValue
+            ]
+        );
+        assert_eq!(
+            db.get(&Path::from_str("root->name")).await.unwrap(),
+            vec!["new"]
+        );
+        assert_eq!(
+            db.get(&Path::from_str("p1->writer")).await.unwrap(),
+            vec!["a"]
+        );
+
+        let kind_v: Vec<Kind> = check(&db, false)
+            .await
+            .unwrap()
+            .iter()
+            .map(|problem| problem.kind)
+            .collect();
+        assert_eq!(
+            kind_v,
+            vec![
+                Kind::DuplicateEmail,
+                Kind::UnknownOwner,
+                Kind::PaperWithoutName
+            ]
+        );
+    }
+}
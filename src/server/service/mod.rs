@@ -1,30 +1,44 @@
-use std::{io, sync::Arc};
+pub mod fsck;
+
+use std::{collections::BTreeMap, io, sync::Arc};
 
 use edge_lib::{
     data::{AsDataManager, Auth},
     util::Path,
     EdgeEngine, ScriptTree,
 };
+use tokio::sync::mpsc;
 
-use crate::err;
+use crate::{
+    data::{
+        self, Aggregate, CacheDataManager, DbDataManager, FindQuery, Format, Hop, Page, PageQuery,
+        Pair, Record, Subgraph, Trashed, Visit,
+    },
+    err,
+};
 
-use super::{crypto, Paper};
+use super::{crypto, GraphQuery, Paper, PathExportQuery};
 
 // Public
-pub async fn register(dm: Arc<dyn AsDataManager>, auth: &crypto::Auth) -> io::Result<()> {
+pub async fn register(
+    dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
+    auth: &crypto::Auth,
+) -> io::Result<()> {
     let key_v = dm.get(&Path::from_str("root->key")).await?;
     if key_v.is_empty() {
         return Err(io::Error::other("no key"));
     }
 
-    if !dm
-        .get(&Path::from_str(&format!("{}<-email", auth.email)))
+    if db
+        .primary()
+        .count(&Path::from_str(&format!("{}<-email", auth.email)))
         .await?
-        .is_empty()
+        > 0
     {
         return Err(io::Error::other("user already exists"));
     }
-    let mut edge_engine = EdgeEngine::new(dm);
+    let mut edge_engine = EdgeEngine::new(dm.divide(dm.get_auth()));
     edge_engine
         .execute1(&ScriptTree {
             script: [
@@ -69,13 +83,14 @@ pub async fn login(dm: Arc<dyn AsDataManager>, auth: &crypto::Auth) -> io::Resul
 
 pub async fn execute(
     dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
     writer: String,
     paper: String,
     pen: String,
     script_vn: &json::JsonValue,
 ) -> err::Result<String> {
     log::info!("executing");
-    if !is_writer_or_higher(&dm, &writer, &paper).await? {
+    if !is_writer_or_higher(&db, &writer, &paper).await? {
         return Err(err::Error::Other(
             "you can not write in this paper".to_string(),
         ));
@@ -96,13 +111,14 @@ pub async fn execute(
 
 pub async fn execute1(
     dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
     writer: String,
     paper: String,
     pen: String,
     script_vn: &ScriptTree,
 ) -> err::Result<String> {
     log::info!("executing");
-    if !is_writer_or_higher(&dm, &writer, &paper).await? {
+    if !is_writer_or_higher(&db, &writer, &paper).await? {
         return Err(err::Error::Other(
             "you can not write in this paper".to_string(),
         ));
@@ -126,6 +142,7 @@ pub async fn put_paper(
     paper: Paper,
 ) -> err::Result<String> {
     log::info!("put_paper");
+    let dm = dm.divide(dm.get_auth());
     let mut edge_engine = EdgeEngine::new(dm.clone());
     let rs = edge_engine
         .execute1(&ScriptTree {
@@ -162,38 +179,143 @@ pub async fn put_paper(
     Ok(paper_id)
 }
 
+/// Moves a paper into the trash of its owner, or removes it for good when `purge` is set.
+///
+/// A paper already in the trash can still be purged by its owner.
 pub async fn delete_paper(
-    dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
+    cache: Option<CacheDataManager>,
     writer: String,
     paper: String,
+    purge: bool,
 ) -> err::Result<()> {
     log::info!("delete_paper");
-    if !is_owner(&dm, &writer, &paper).await? {
+    let is_trashed = purge
+        && db
+            .trash_owner(&paper)
+            .await
+            .map_err(|e| err::Error::Other(e.to_string()))?
+            .is_some_and(|owner| owner == writer);
+    if !is_trashed && !is_owner(&db, &writer, &paper).await? {
         return Err(err::Error::Other(
             "you can not delete this paper".to_string(),
         ));
     }
-    let mut edge_engine = EdgeEngine::new(dm);
-    edge_engine
-        .execute1(&ScriptTree {
-            script: [
-                format!("{paper}->writer = _ _"),
-                format!("{paper}->manager = _ _"),
-                format!("{writer}->paper left {writer}->paper {paper}"),
-            ]
-            .join("\n"),
-            name: "result".to_string(),
-            next_v: vec![],
-        })
+    if purge {
+        db.purge_paper(&paper).await.map(|_| ())
+    } else {
+        db.trash_paper(&paper, &writer).await
+    }
+    .map_err(|e| err::Error::Other(e.to_string()))?;
+    if let Some(cache) = cache {
+        cache.invalidate_all();
+    }
+    Ok(())
+}
+
+pub async fn restore_paper(
+    db: DbDataManager,
+    cache: Option<CacheDataManager>,
+    writer: String,
+    paper: String,
+) -> err::Result<()> {
+    log::info!("restore_paper");
+    let owner = db
+        .trash_owner(&paper)
         .await
         .map_err(|e| err::Error::Other(e.to_string()))?;
-    edge_engine
-        .commit()
+    if owner.as_deref() != Some(writer.as_str()) {
+        return Err(err::Error::Other(
+            "you can not restore this paper".to_string(),
+        ));
+    }
+    db.restore_paper(&paper)
         .await
         .map_err(|e| err::Error::Other(e.to_string()))?;
+    if let Some(cache) = cache {
+        cache.invalidate_all();
+    }
     Ok(())
 }
 
+pub async fn export_paper(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+) -> err::Result<mpsc::Receiver<io::Result<Record>>> {
+    if paper.is_empty() || !is_writer_or_higher(&db, &writer, &paper).await? {
+        return Err(err::Error::Other("you can not read this paper".to_string()));
+    }
+    Ok(db.export_paper(&paper))
+}
+
+/// Exports the edges of a paper as one text in `format`.
+pub async fn export_paper_text(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    format: Format,
+) -> err::Result<String> {
+    let mut receiver = export_paper(db, writer, paper).await?;
+    let mut hop_v = Vec::new();
+    while let Some(record) = receiver.recv().await {
+        let record = record.map_err(|e| err::Error::Other(e.to_string()))?;
+        hop_v.push(Hop::from(record));
+    }
+    Ok(data::to_text(&hop_v, format))
+}
+
+/// Imports records in `format` into a paper, returning the id each node got.
+///
+/// Every edge is written by `pen`, whatever pen the records name, so that a writer can
+/// not sign edges as someone else.
+#[allow(clippy::too_many_arguments)]
+pub async fn import_paper(
+    db: DbDataManager,
+    cache: Option<CacheDataManager>,
+    writer: String,
+    pen: String,
+    paper: String,
+    from: Option<String>,
+    format: Format,
+    text: &str,
+) -> err::Result<BTreeMap<String, String>> {
+    log::info!("import_paper");
+    if paper.is_empty() || !is_writer_or_higher(&db, &writer, &paper).await? {
+        return Err(err::Error::Other(
+            "you can not write this paper".to_string(),
+        ));
+    }
+    let mut record_v = match format {
+        Format::Jsonl => data::parse_jsonl(text),
+        Format::Ntriples => data::parse_ntriples(text, &pen),
+        Format::Turtle | Format::Dot => {
+            return Err(err::Error::Other(format!(
+                "can not import {}",
+                format.content_type()
+            )))
+        }
+    }
+    .map_err(|e| err::Error::Other(e.to_string()))?;
+    for record in &mut record_v {
+        record.pen = pen.clone();
+    }
+    let id_map = db
+        .import_paper(&paper, from.as_deref(), record_v)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))?;
+    if let Some(cache) = cache {
+        cache.invalidate_all();
+    }
+    Ok(id_map)
+}
+
+pub async fn list_trash(db: DbDataManager, writer: String) -> err::Result<Vec<Trashed>> {
+    db.list_trash(&writer)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
 pub async fn get_paper(dm: Arc<dyn AsDataManager>, writer: String) -> err::Result<String> {
     let mut edge_engine = EdgeEngine::new(dm);
     let rs = edge_engine
@@ -224,10 +346,11 @@ pub async fn get_paper(dm: Arc<dyn AsDataManager>, writer: String) -> err::Resul
 
 pub async fn get_paper_writer(
     dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
     writer: String,
     paper_id: String,
 ) -> err::Result<String> {
-    if !is_writer_or_higher(&dm, &writer, &paper_id).await? {
+    if !is_writer_or_higher(&db, &writer, &paper_id).await? {
         return Err(err::Error::Other("you can not read this paper".to_string()));
     }
     let mut edge_engine = EdgeEngine::new(dm);
@@ -248,11 +371,13 @@ pub async fn get_paper_writer(
 
 pub async fn update_paper(
     dm: Arc<dyn AsDataManager>,
+    db: DbDataManager,
     writer: String,
     paper: Paper,
 ) -> err::Result<()> {
+    let dm = dm.divide(dm.get_auth());
     let mut edge_engine = EdgeEngine::new(dm.clone());
-    if is_owner(&dm, &writer, &paper.paper_id).await? {
+    if is_owner(&db, &writer, &paper.paper_id).await? {
         edge_engine
             .execute1(&ScriptTree {
                 script: format!("{}->name = {} _", paper.paper_id, paper.name),
@@ -273,7 +398,7 @@ pub async fn update_paper(
         )
         .await
         .map_err(|e| err::Error::Other(e.to_string()))?;
-    } else if is_manager(&dm, &writer, &paper.paper_id).await? {
+    } else if is_manager(&db, &writer, &paper.paper_id).await? {
         dm.set(
             &Path::from_str(&format!("{}->writer", paper.paper_id)),
             paper.writer_v,
@@ -292,69 +417,207 @@ pub async fn update_paper(
     Ok(())
 }
 
+pub async fn get_page(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    pen: String,
+    path: &str,
+    query: &PageQuery,
+) -> err::Result<Page> {
+    paper_db(&db, &writer, &paper, &pen)
+        .await?
+        .get_page(&Path::from_str(path), query)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn get_pair_page(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    pen: String,
+    path: &str,
+    query: &PageQuery,
+) -> err::Result<Page<Pair>> {
+    paper_db(&db, &writer, &paper, &pen)
+        .await?
+        .get_pair_page(&Path::from_str(path), query)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn find(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    pen: String,
+    find: &FindQuery,
+    query: &PageQuery,
+) -> err::Result<Page<Hop>> {
+    paper_db(&db, &writer, &paper, &pen)
+        .await?
+        .find(find, query)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn aggregate(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    pen: String,
+    path: &str,
+    group_by: Option<usize>,
+) -> err::Result<Vec<Aggregate>> {
+    paper_db(&db, &writer, &paper, &pen)
+        .await?
+        .aggregate(&Path::from_str(path), group_by)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn stream(
+    db: DbDataManager,
+    writer: String,
+    paper: String,
+    pen: String,
+    path: &str,
+) -> err::Result<mpsc::Receiver<io::Result<String>>> {
+    Ok(paper_db(&db, &writer, &paper, &pen)
+        .await?
+        .stream(&Path::from_str(path)))
+}
+
+pub async fn bfs(
+    db: DbDataManager,
+    writer: String,
+    pen: String,
+    query: &GraphQuery,
+) -> err::Result<Vec<Visit>> {
+    paper_db(&db, &writer, &query.paper, &pen)
+        .await?
+        .bfs(&query.from, &query.search)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn shortest_path(
+    db: DbDataManager,
+    writer: String,
+    pen: String,
+    query: &GraphQuery,
+) -> err::Result<Option<Vec<Hop>>> {
+    paper_db(&db, &writer, &query.paper, &pen)
+        .await?
+        .shortest_path(&query.from, &query.to, &query.search)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn neighborhood(
+    db: DbDataManager,
+    writer: String,
+    pen: String,
+    query: &GraphQuery,
+) -> err::Result<Subgraph> {
+    paper_db(&db, &writer, &query.paper, &pen)
+        .await?
+        .neighborhood(&query.from, &query.search)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn is_reachable(
+    db: DbDataManager,
+    writer: String,
+    pen: String,
+    query: &GraphQuery,
+) -> err::Result<bool> {
+    paper_db(&db, &writer, &query.paper, &pen)
+        .await?
+        .is_reachable(&query.from, &query.to, &query.search)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+pub async fn path_subgraph(
+    db: DbDataManager,
+    writer: String,
+    pen: String,
+    query: &PathExportQuery,
+) -> err::Result<Subgraph> {
+    paper_db(&db, &writer, &query.paper, &pen)
+        .await?
+        .path_subgraph(&Path::from_str(&query.path), &query.search)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+/// Returns what `pen` sees in `paper` when `writer` may read it.
+async fn paper_db(
+    db: &DbDataManager,
+    writer: &str,
+    paper: &str,
+    pen: &str,
+) -> err::Result<DbDataManager> {
+    if !is_writer_or_higher(db, writer, paper).await? {
+        return Err(err::Error::Other("you can not read this paper".to_string()));
+    }
+    Ok(db.with_auth(Auth::writer(paper, pen)))
+}
+
 async fn is_writer_or_higher(
-    dm: &Arc<dyn AsDataManager>,
-    writer: &String,
+    db: &DbDataManager,
+    writer: &str,
     paper_id: &str,
 ) -> err::Result<bool> {
-    if is_writer(dm, writer, paper_id).await? {
+    if is_writer(db, writer, paper_id).await? {
         return Ok(true);
     }
-    is_manager_or_higher(dm, writer, paper_id).await
+    is_manager_or_higher(db, writer, paper_id).await
 }
 
 async fn is_manager_or_higher(
-    dm: &Arc<dyn AsDataManager>,
-    writer: &String,
+    db: &DbDataManager,
+    writer: &str,
     paper_id: &str,
 ) -> err::Result<bool> {
-    if is_manager(dm, writer, paper_id).await? {
+    if is_manager(db, writer, paper_id).await? {
         return Ok(true);
     }
-    is_owner(dm, writer, paper_id).await
+    is_owner(db, writer, paper_id).await
 }
 
-async fn is_writer(
-    dm: &Arc<dyn AsDataManager>,
-    writer: &String,
-    paper_id: &str,
-) -> err::Result<bool> {
+/// Whether `writer` is a target of `path`, read on the primary.
+///
+/// Roles are read past the replicas, which may lag behind a role revoked a moment ago, and
+/// past the cache, which misses what other processes revoke. Either would let the former
+/// holder in.
+async fn has_role(db: &DbDataManager, path: &str, writer: &str) -> err::Result<bool> {
+    db.primary()
+        .contains(&Path::from_str(path), writer)
+        .await
+        .map_err(|e| err::Error::Other(e.to_string()))
+}
+
+async fn is_writer(db: &DbDataManager, writer: &str, paper_id: &str) -> err::Result<bool> {
     if paper_id.is_empty() {
         return Ok(true);
     }
-    let writer_v = dm
-        .get(&Path::from_str(&format!("{paper_id}->writer")))
-        .await
-        .map_err(|e| err::Error::Other(e.to_string()))?;
-    Ok(writer_v.contains(writer))
+    has_role(db, &format!("{paper_id}->writer"), writer).await
 }
 
-async fn is_manager(
-    dm: &Arc<dyn AsDataManager>,
-    writer: &String,
-    paper_id: &str,
-) -> err::Result<bool> {
+async fn is_manager(db: &DbDataManager, writer: &str, paper_id: &str) -> err::Result<bool> {
     if paper_id.is_empty() {
         return Ok(false);
     }
-    let manager_v = dm
-        .get(&Path::from_str(&format!("{paper_id}->manager")))
-        .await
-        .map_err(|e| err::Error::Other(e.to_string()))?;
-    Ok(manager_v.contains(writer))
+    has_role(db, &format!("{paper_id}->manager"), writer).await
 }
 
-async fn is_owner(
-    dm: &Arc<dyn AsDataManager>,
-    writer: &String,
-    paper_id: &str,
-) -> err::Result<bool> {
+async fn is_owner(db: &DbDataManager, writer: &str, paper_id: &str) -> err::Result<bool> {
     if paper_id.is_empty() {
         return Ok(false);
     }
-    let owner_v = dm
-        .get(&Path::from_str(&format!("{paper_id}<-paper")))
-        .await
-        .map_err(|e| err::Error::Other(e.to_string()))?;
-    Ok(owner_v.contains(writer))
+    has_role(db, &format!("{paper_id}<-paper"), writer).await
 }